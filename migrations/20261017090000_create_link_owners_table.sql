@@ -0,0 +1,12 @@
+CREATE TABLE link_owners(
+   id uuid NOT NULL,
+   PRIMARY KEY (id),
+   -- sha256 of the management token, the token itself is only shown once
+   -- to the creator of the link
+   management_token_hash TEXT NOT NULL UNIQUE,
+   created_at timestamptz NOT NULL
+);
+
+-- allow null for links created before owners existed
+ALTER TABLE links ADD COLUMN owner_id uuid NULL REFERENCES link_owners (id);
+ALTER TABLE links ADD COLUMN disabled BOOLEAN NOT NULL DEFAULT false;
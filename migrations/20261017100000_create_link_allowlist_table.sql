@@ -0,0 +1,6 @@
+CREATE TABLE link_allowlist(
+   link_id TEXT NOT NULL REFERENCES links (id) ON DELETE CASCADE,
+   -- either an exact email or a whole domain prefixed with `@`
+   pattern TEXT NOT NULL,
+   PRIMARY KEY (link_id, pattern)
+);
@@ -0,0 +1,6 @@
+-- where approval requests are sent, optional as long as the owner doesn't
+-- require approving recipients
+ALTER TABLE link_owners ADD COLUMN email TEXT NULL;
+ALTER TABLE links ADD COLUMN requires_approval BOOLEAN NOT NULL DEFAULT false;
+-- handed to the owner to approve or deny a recipient who confirmed their email
+ALTER TABLE links_tokens ADD COLUMN approval_token TEXT NULL UNIQUE;
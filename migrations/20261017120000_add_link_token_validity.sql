@@ -0,0 +1,4 @@
+-- hours a confirmation email stays valid, and hours a confirmed recipient
+-- keeps access, the application wide settings are used when null
+ALTER TABLE links ADD COLUMN link_token_validity_hours INT NULL;
+ALTER TABLE links ADD COLUMN grant_validity_hours INT NULL;
@@ -0,0 +1,3 @@
+-- null means the link is available right away, and never expires
+ALTER TABLE links ADD COLUMN active_from timestamptz NULL;
+ALTER TABLE links ADD COLUMN expires_at timestamptz NULL;
@@ -0,0 +1,5 @@
+-- null means there is no limit
+ALTER TABLE links ADD COLUMN max_clicks INT NULL;
+ALTER TABLE links ADD COLUMN max_recipients INT NULL;
+-- successful redirects to the target url
+ALTER TABLE links ADD COLUMN click_count INT NOT NULL DEFAULT 0;
@@ -0,0 +1,13 @@
+CREATE TABLE link_events(
+   id uuid NOT NULL,
+   PRIMARY KEY (id),
+   link_id TEXT NOT NULL REFERENCES links (id) ON DELETE CASCADE,
+   -- view, registration, confirmation, redirect
+   kind TEXT NOT NULL,
+   -- null for views, nobody has registered yet
+   recipient_id uuid NULL REFERENCES link_recipients (id),
+   user_agent TEXT NULL,
+   referrer TEXT NULL,
+   created_at timestamptz NOT NULL
+);
+CREATE INDEX link_events_link_id_created_at ON link_events (link_id, created_at);
@@ -0,0 +1,13 @@
+CREATE TABLE api_keys(
+   id uuid NOT NULL,
+   PRIMARY KEY (id),
+   owner_id uuid NOT NULL REFERENCES link_owners (id) ON DELETE CASCADE,
+   name TEXT NOT NULL,
+   -- sha256 of the key, the key itself is only shown once
+   key_hash TEXT NOT NULL UNIQUE,
+   -- e.g. links:create, recipients:read
+   scopes TEXT[] NOT NULL,
+   created_at timestamptz NOT NULL
+);
+-- the api key that created the link, null for links created from the html form
+ALTER TABLE links ADD COLUMN api_key_id uuid NULL REFERENCES api_keys (id);
@@ -0,0 +1,2 @@
+-- drives the `counter` id generator
+CREATE SEQUENCE link_id_counter;
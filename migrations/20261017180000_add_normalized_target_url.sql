@@ -0,0 +1,4 @@
+-- `NormalizedUrl` of the target, links created before it stay null and are
+-- never deduplicated
+ALTER TABLE links ADD COLUMN normalized_target_url TEXT NULL;
+CREATE INDEX links_owner_id_normalized_target_url ON links (owner_id, normalized_target_url);
@@ -0,0 +1,6 @@
+-- Links can show recipients a preview page with the destination host and
+-- notes of the owner before redirecting them
+ALTER TABLE links
+    ADD COLUMN show_preview BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN title TEXT NULL,
+    ADD COLUMN description TEXT NULL;
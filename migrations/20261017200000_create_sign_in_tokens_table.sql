@@ -0,0 +1,11 @@
+CREATE TABLE sign_in_tokens(
+   -- sha256 of the token, the token itself is only sent by email
+   token_hash TEXT NOT NULL,
+   PRIMARY KEY (token_hash),
+   recipient_id uuid NOT NULL REFERENCES link_recipients (id) ON DELETE CASCADE,
+   link_id TEXT NOT NULL REFERENCES links (id) ON DELETE CASCADE,
+   expires_at timestamptz NOT NULL,
+   -- sign-in links work once
+   used_at timestamptz NULL,
+   created_at timestamptz NOT NULL
+);
@@ -0,0 +1,7 @@
+-- statuses used to be free form text
+CREATE TYPE link_token_status AS ENUM ('pending', 'awaiting_approval', 'confirmed', 'denied');
+ALTER TABLE links_tokens
+    ALTER COLUMN status TYPE link_token_status USING status::link_token_status;
+-- link tokens are used once, tokens that already left `pending` count as used
+ALTER TABLE links_tokens ADD COLUMN confirmed_at timestamptz NULL;
+UPDATE links_tokens SET confirmed_at = now() WHERE status <> 'pending';
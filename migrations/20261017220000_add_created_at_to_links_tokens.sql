@@ -0,0 +1,3 @@
+-- when the confirmation email of a link token was sent, resending waits for a
+-- cooldown after the latest one
+ALTER TABLE links_tokens ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
@@ -0,0 +1,19 @@
+-- emails are written along with what they are about and delivered by a
+-- background worker, so a slow or failing email api doesn't fail requests
+CREATE TYPE email_outbox_status AS ENUM ('pending', 'sent', 'failed');
+CREATE TABLE email_outbox(
+   id uuid NOT NULL,
+   PRIMARY KEY (id),
+   recipient TEXT NOT NULL,
+   subject TEXT NOT NULL,
+   html_body TEXT NOT NULL,
+   text_body TEXT NOT NULL,
+   status email_outbox_status NOT NULL DEFAULT 'pending',
+   attempts INT NOT NULL DEFAULT 0,
+   -- pushed back after every failed attempt
+   next_attempt_at timestamptz NOT NULL,
+   last_error TEXT NULL,
+   created_at timestamptz NOT NULL,
+   sent_at timestamptz NULL
+);
+CREATE INDEX email_outbox_pending_idx ON email_outbox (next_attempt_at) WHERE status = 'pending';
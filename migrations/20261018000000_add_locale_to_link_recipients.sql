@@ -0,0 +1,3 @@
+-- the language of their emails, from the `Accept-Language` of their latest
+-- registration
+ALTER TABLE link_recipients ADD COLUMN locale TEXT NOT NULL DEFAULT 'en';
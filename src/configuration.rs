@@ -1,16 +1,23 @@
+use anyhow::Context;
 use config::{Config, ConfigError};
 use secrecy::{ExposeSecret, SecretString};
 use serde::Deserialize;
-use serde_aux::field_attributes::deserialize_number_from_string;
+use serde_aux::field_attributes::{
+    deserialize_number_from_string, deserialize_option_number_from_string,
+};
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 
-use crate::domain::RecipientEmail;
+use crate::{
+    domain::{ApplicationBaseUrl, RecipientEmail},
+    email_client::{EmailClient, FileTransport, PostmarkTransport, SmtpTransport},
+};
 
 #[derive(Deserialize, Clone)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
+    pub email_outbox: EmailOutboxSettings,
 }
 
 #[derive(Deserialize, Clone)]
@@ -21,7 +28,96 @@ pub struct ApplicationSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
     pub host: String,
-    pub base_url: String,
+    /// Where the application is reachable from the outside, with the port
+    /// and path prefix if there are any.
+    pub base_url: ApplicationBaseUrl,
+    /// How long a confirmation link stays valid, links can override it.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub link_token_validity_hours: i32,
+    /// How long a recipient waits before another confirmation or sign-in
+    /// email is sent for the same link.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub resend_cooldown_seconds: i64,
+    /// How long a confirmed recipient keeps access to a link, links can
+    /// override it. Access never expires when it's missing.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub grant_validity_hours: Option<i32>,
+    pub id_generator: IdGeneratorSettings,
+    /// Signs the cookies remembering verified recipients, at least 64 bytes.
+    pub hmac_secret: SecretString,
+    /// How long a verified recipient is remembered by their browser.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub recipient_session_hours: i64,
+    /// Where recipients land once they confirmed their email.
+    #[serde(default)]
+    pub after_confirmation: AfterConfirmation,
+    /// How returning recipients get access to a link.
+    #[serde(default)]
+    pub sign_in: SignInMode,
+    /// How long a magic sign-in link stays valid.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub sign_in_token_validity_minutes: i64,
+    /// File of domains links may not point to, one per line.
+    #[serde(default)]
+    pub blocklist_path: Option<String>,
+}
+
+impl ApplicationSettings {
+    /// Blank lines and `#` comments are skipped.
+    pub fn blocklist(&self) -> Result<Vec<String>, std::io::Error> {
+        let Some(path) = &self.blocklist_path else {
+            return Ok(Vec::new());
+        };
+        let blocklist = std::fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect();
+        Ok(blocklist)
+    }
+}
+
+/// What `confirm` shows a recipient who was just granted access.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AfterConfirmation {
+    /// A page telling them to go back to the short link.
+    #[default]
+    SuccessPage,
+    /// The same page with a button to the short link.
+    ContinuePage,
+    /// Straight to the target url.
+    Redirect,
+}
+
+/// How returning recipients prove who they are.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SignInMode {
+    /// Typing the name and email they registered with.
+    #[default]
+    NameAndEmail,
+    /// Clicking a one time sign-in link sent to their email.
+    MagicLink,
+}
+
+/// How short link ids are generated, see `IdGenerator`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IdGeneratorSettings {
+    Random {
+        #[serde(deserialize_with = "deserialize_number_from_string")]
+        length: usize,
+    },
+    Counter {
+        #[serde(deserialize_with = "deserialize_number_from_string")]
+        key: u64,
+    },
+    Words {
+        #[serde(deserialize_with = "deserialize_number_from_string")]
+        count: usize,
+    },
 }
 
 #[derive(Deserialize, Clone)]
@@ -54,13 +150,93 @@ impl DatabaseSettings {
 
 #[derive(serde::Deserialize, Clone)]
 pub struct EmailClientSettings {
-    pub base_url: String,
     pub sender_email: String,
-    pub authorization_token: SecretString,
     pub timeout_milliseconds: u64,
+    pub transport: EmailTransportSettings,
+}
+
+/// Where emails go, see `EmailTransport`.
+#[derive(Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EmailTransportSettings {
+    /// Postmark's email api.
+    Postmark {
+        base_url: String,
+        authorization_token: SecretString,
+    },
+    /// Any smtp server supporting STARTTLS, credentials are optional.
+    Smtp {
+        host: String,
+        #[serde(deserialize_with = "deserialize_number_from_string")]
+        port: u16,
+        #[serde(default)]
+        username: Option<String>,
+        #[serde(default)]
+        password: Option<SecretString>,
+    },
+    /// `.eml` files written to a directory, nothing is sent.
+    File { directory: String },
+}
+
+/// How the background worker goes through the email outbox.
+#[derive(Deserialize, Clone)]
+pub struct EmailOutboxSettings {
+    /// How long the worker sleeps once the outbox is empty.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub poll_interval_milliseconds: u64,
+    /// Emails are marked as failed after this many attempts.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts: i32,
+    /// Wait after the first failed attempt, doubled after each one.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retry_base_delay_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retry_max_delay_milliseconds: u64,
+}
+
+impl EmailOutboxSettings {
+    pub fn poll_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.poll_interval_milliseconds)
+    }
+
+    pub fn retry_base_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.retry_base_delay_milliseconds)
+    }
+
+    pub fn retry_max_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.retry_max_delay_milliseconds)
+    }
 }
 
 impl EmailClientSettings {
+    pub fn client(self) -> anyhow::Result<EmailClient> {
+        let sender_email = self.sender().map_err(anyhow::Error::msg)?;
+        let timeout = self.timeout();
+        let email_client = match self.transport {
+            EmailTransportSettings::Postmark {
+                base_url,
+                authorization_token,
+            } => EmailClient::new(
+                sender_email,
+                PostmarkTransport::new(&base_url, authorization_token, timeout)
+                    .with_context(|| format!("{} is not a valid postmark base url", base_url))?,
+            ),
+            EmailTransportSettings::Smtp {
+                host,
+                port,
+                username,
+                password,
+            } => EmailClient::new(
+                sender_email,
+                SmtpTransport::new(&host, port, username.zip(password), timeout)?,
+            ),
+            EmailTransportSettings::File { directory } => {
+                EmailClient::new(sender_email, FileTransport::new(directory)?)
+            }
+        };
+        Ok(email_client)
+    }
+
     pub fn sender(&self) -> Result<RecipientEmail, String> {
         RecipientEmail::parse(self.sender_email.clone())
     }
@@ -0,0 +1,112 @@
+use super::RecipientEmail;
+
+/// An entry of the allowlist of recipients who may register for a link.
+#[derive(Debug, PartialEq)]
+pub enum AllowlistEntry {
+    /// A single exact email, e.g. `hamada@yahoo.com`.
+    Email(String),
+    /// Every email of a domain, written with a leading `@`, e.g. `@ourcompany.com`.
+    Domain(String),
+}
+
+impl AllowlistEntry {
+    pub fn parse(s: String) -> Result<AllowlistEntry, String> {
+        let s = s.trim().to_lowercase();
+        match s.strip_prefix('@') {
+            Some(domain) => {
+                let is_valid_domain = domain.contains('.')
+                    && !domain.starts_with('.')
+                    && !domain.ends_with('.')
+                    && domain
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+                if is_valid_domain {
+                    Ok(Self::Domain(domain.to_string()))
+                } else {
+                    Err(format!("@{} is not a valid email domain.", domain))
+                }
+            }
+            None => Ok(Self::Email(RecipientEmail::parse(s)?.as_ref().to_string())),
+        }
+    }
+
+    /// Parse a comma, space or newline separated list of entries.
+    pub fn parse_list(s: &str) -> Result<Vec<AllowlistEntry>, String> {
+        s.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| Self::parse(entry.to_string()))
+            .collect()
+    }
+
+    pub fn allows(&self, email: &RecipientEmail) -> bool {
+        let email = email.as_ref().to_lowercase();
+        match self {
+            Self::Email(allowed) => *allowed == email,
+            Self::Domain(domain) => email
+                .rsplit_once('@')
+                .is_some_and(|(_, email_domain)| email_domain == domain),
+        }
+    }
+}
+
+impl std::fmt::Display for AllowlistEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Email(email) => write!(f, "{}", email),
+            Self::Domain(domain) => write!(f, "@{}", domain),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AllowlistEntry;
+    use crate::domain::RecipientEmail;
+    use claims::{assert_err, assert_ok};
+
+    fn email(s: &str) -> RecipientEmail {
+        RecipientEmail::parse(s.to_string()).unwrap()
+    }
+
+    #[test]
+    fn a_domain_entry_is_parsed_successfully() {
+        assert_eq!(
+            AllowlistEntry::parse("@OurCompany.com".to_string()),
+            Ok(AllowlistEntry::Domain("ourcompany.com".to_string()))
+        );
+    }
+
+    #[test]
+    fn an_email_entry_is_parsed_successfully() {
+        assert_ok!(AllowlistEntry::parse("hamada@yahoo.com".to_string()));
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected() {
+        for entry in ["@", "@localhost", "@.com", "@our company.com", "hamada"] {
+            assert_err!(AllowlistEntry::parse(entry.to_string()));
+        }
+    }
+
+    #[test]
+    fn a_list_of_entries_is_split_on_commas_and_whitespace() {
+        let entries =
+            AllowlistEntry::parse_list("@ourcompany.com, hamada@yahoo.com\n@partner.org").unwrap();
+        assert_eq!(entries.len(), 3);
+    }
+
+    #[test]
+    fn a_domain_entry_allows_only_emails_of_that_domain() {
+        let entry = AllowlistEntry::parse("@ourcompany.com".to_string()).unwrap();
+        assert!(entry.allows(&email("hamada@OurCompany.com")));
+        assert!(!entry.allows(&email("hamada@notourcompany.com")));
+        assert!(!entry.allows(&email("hamada@ourcompany.com.evil.org")));
+    }
+
+    #[test]
+    fn an_email_entry_allows_only_that_email() {
+        let entry = AllowlistEntry::parse("hamada@yahoo.com".to_string()).unwrap();
+        assert!(entry.allows(&email("Hamada@yahoo.com")));
+        assert!(!entry.allows(&email("depp@yahoo.com")));
+    }
+}
@@ -0,0 +1,163 @@
+use serde::Deserialize;
+use url::Url;
+
+/// Where the application is reachable from the outside, every absolute link
+/// put in an email or an api response is built from it.
+///
+/// It may carry a port and a path prefix, `https://example.com/go` builds
+/// short links like `https://example.com/go/abc1234`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ApplicationBaseUrl(Url);
+
+impl ApplicationBaseUrl {
+    pub fn parse(s: &str) -> Result<ApplicationBaseUrl, String> {
+        let url =
+            Url::parse(s.trim()).map_err(|e| format!("{} is not a valid base url, {}", s, e))?;
+
+        if !["http", "https"].contains(&url.scheme()) {
+            return Err(format!("{} has to be an http or https url", s));
+        }
+        if url.host_str().is_none() {
+            return Err(format!("{} has no host", s));
+        }
+        if url.query().is_some() || url.fragment().is_some() {
+            return Err(format!("{} can't have a query or a fragment", s));
+        }
+
+        Ok(Self(url))
+    }
+
+    /// The host alone, without the scheme, port or path prefix.
+    pub fn host(&self) -> &str {
+        self.0.host_str().unwrap_or_default()
+    }
+
+    /// Browsers drop secure cookies sent over plain http.
+    pub fn is_https(&self) -> bool {
+        self.0.scheme() == "https"
+    }
+
+    /// The path prefix without its trailing slash, empty when there is none,
+    /// the pages start their own urls with it.
+    pub fn base_path(&self) -> &str {
+        self.0.path().trim_end_matches('/')
+    }
+
+    pub fn short_link(&self, link_id: &str) -> Url {
+        self.path(&[link_id])
+    }
+
+    /// Where the continue button of the preview page leads.
+    pub fn continue_link(&self, link_id: &str) -> Url {
+        self.path(&[link_id, "continue"])
+    }
+
+    pub fn confirmation_link(&self, link_token: &str) -> Url {
+        self.path_with_query(&["link_recipients", "confirm"], "link_token", link_token)
+    }
+
+    pub fn approval_link(&self, approval_token: &str) -> Url {
+        self.path_with_query(
+            &["link_recipients", "approve"],
+            "approval_token",
+            approval_token,
+        )
+    }
+
+    pub fn denial_link(&self, approval_token: &str) -> Url {
+        self.path_with_query(
+            &["link_recipients", "deny"],
+            "approval_token",
+            approval_token,
+        )
+    }
+
+    pub fn sign_in_link(&self, token: &str) -> Url {
+        self.path_with_query(&["sign_in", "confirm"], "token", token)
+    }
+
+    /// The segments go after the path prefix, with or without its trailing
+    /// slash, and are percent encoded when needed.
+    fn path(&self, segments: &[&str]) -> Url {
+        let mut url = self.0.clone();
+        url.path_segments_mut()
+            .expect("http urls always have a path")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    fn path_with_query(&self, segments: &[&str], key: &str, value: &str) -> Url {
+        let mut url = self.path(segments);
+        url.query_pairs_mut().append_pair(key, value);
+        url
+    }
+}
+
+impl TryFrom<String> for ApplicationBaseUrl {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::parse(&s)
+    }
+}
+
+impl AsRef<str> for ApplicationBaseUrl {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::ApplicationBaseUrl;
+    use claims::assert_err;
+
+    #[test]
+    fn links_keep_the_port_and_path_prefix() {
+        for base_url in [
+            "https://example.com:8443/go",
+            "https://example.com:8443/go/",
+        ] {
+            let base_url = ApplicationBaseUrl::parse(base_url).unwrap();
+            assert_eq!(
+                base_url.short_link("abc1234").as_str(),
+                "https://example.com:8443/go/abc1234"
+            );
+            assert_eq!(
+                base_url.confirmation_link("token").as_str(),
+                "https://example.com:8443/go/link_recipients/confirm?link_token=token"
+            );
+            assert_eq!(base_url.base_path(), "/go");
+        }
+    }
+
+    #[test]
+    fn links_without_a_prefix_start_at_the_root() {
+        let base_url = ApplicationBaseUrl::parse("http://127.0.0.1:8080").unwrap();
+        assert_eq!(
+            base_url.sign_in_link("token").as_str(),
+            "http://127.0.0.1:8080/sign_in/confirm?token=token"
+        );
+        assert_eq!(base_url.host(), "127.0.0.1");
+        assert_eq!(base_url.base_path(), "");
+        assert!(!base_url.is_https());
+    }
+
+    #[test]
+    fn invalid_base_urls_are_rejected() {
+        for base_url in [
+            "127.0.0.1",
+            "ftp://example.com",
+            "https://example.com/?a=b",
+            "https://example.com/#top",
+        ] {
+            assert_err!(
+                ApplicationBaseUrl::parse(base_url),
+                "{} was accepted",
+                base_url
+            );
+        }
+    }
+}
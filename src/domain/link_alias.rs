@@ -0,0 +1,107 @@
+/// Paths already taken by the application, an alias can't shadow them.
+const RESERVED_ALIASES: [&str; 10] = [
+    "create",
+    "health_check",
+    "templates",
+    "link_recipients",
+    "get_link",
+    "manage",
+    "api",
+    "sign_in",
+    "favicon.ico",
+    "robots.txt",
+];
+
+/// A custom short link id chosen by the owner, e.g. `/q3-roadmap`.
+#[derive(Debug)]
+pub struct LinkAlias(String);
+
+impl LinkAlias {
+    pub fn parse(s: String) -> Result<LinkAlias, String> {
+        let length = s.chars().count();
+        let is_too_short = length < 3;
+        let is_too_long = length > 64;
+
+        // only what can be put in a path as is, without percent encoding
+        let contains_forbidden_characters = s
+            .chars()
+            .any(|c| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+
+        if is_too_short || is_too_long || contains_forbidden_characters {
+            Err(format!(
+                "{} is not a valid alias, use 3 to 64 letters, digits, `-` or `_`",
+                s
+            ))
+        } else if Self::is_reserved(&s) {
+            Err(format!("{} is reserved, choose another alias", s))
+        } else {
+            Ok(Self(s))
+        }
+    }
+
+    /// Whether `id` is a path of the application, a link with it as id
+    /// could never be opened.
+    pub fn is_reserved(id: &str) -> bool {
+        RESERVED_ALIASES
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(id))
+    }
+}
+
+impl AsRef<str> for LinkAlias {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::link_alias::LinkAlias;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn a_valid_alias_is_parsed_successfully() {
+        assert_ok!(LinkAlias::parse("q3-roadmap".to_string()));
+        assert_ok!(LinkAlias::parse("Release_2025".to_string()));
+    }
+
+    #[test]
+    fn aliases_outside_the_length_bounds_are_rejected() {
+        assert_err!(LinkAlias::parse("ab".to_string()));
+        assert_err!(LinkAlias::parse("a".repeat(65)));
+        assert_ok!(LinkAlias::parse("a".repeat(64)));
+    }
+
+    #[test]
+    fn aliases_containing_an_invalid_character_are_rejected() {
+        for alias in [
+            "q3/roadmap",
+            "q3 roadmap",
+            "q3.roadmap",
+            "roadmap?",
+            "café-menu",
+        ] {
+            assert_err!(LinkAlias::parse(alias.to_string()));
+        }
+    }
+
+    #[test]
+    fn reserved_aliases_are_rejected_whatever_their_case() {
+        for alias in [
+            "create",
+            "health_check",
+            "templates",
+            "Link_Recipients",
+            "GET_LINK",
+        ] {
+            assert_err!(LinkAlias::parse(alias.to_string()));
+        }
+    }
+
+    #[test]
+    fn short_paths_of_the_application_are_reserved() {
+        assert!(LinkAlias::is_reserved("api"));
+        assert!(LinkAlias::is_reserved("Create"));
+        assert!(!LinkAlias::is_reserved("apis"));
+    }
+}
@@ -0,0 +1,48 @@
+use serde::Serialize;
+
+/// Where a recipient stands with a link, the `link_token_status` enum of the
+/// `status` column of `links_tokens`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, sqlx::Type)]
+#[sqlx(type_name = "link_token_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum LinkTokenStatus {
+    /// Registered, the confirmation email was sent.
+    Pending,
+    /// Confirmed their email, the owner still has to approve them.
+    AwaitingApproval,
+    /// Has access to the link.
+    Confirmed,
+    /// The owner refused them access.
+    Denied,
+}
+
+impl LinkTokenStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkTokenStatus::Pending => "pending",
+            LinkTokenStatus::AwaitingApproval => "awaiting_approval",
+            LinkTokenStatus::Confirmed => "confirmed",
+            LinkTokenStatus::Denied => "denied",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::LinkTokenStatus;
+
+    #[test]
+    fn as_str_matches_the_serialized_name() {
+        for status in [
+            LinkTokenStatus::Pending,
+            LinkTokenStatus::AwaitingApproval,
+            LinkTokenStatus::Confirmed,
+            LinkTokenStatus::Denied,
+        ] {
+            assert_eq!(
+                serde_json::to_value(status).unwrap(),
+                serde_json::Value::from(status.as_str())
+            );
+        }
+    }
+}
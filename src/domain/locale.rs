@@ -0,0 +1,83 @@
+/// A language emails can be written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Fr,
+}
+
+const SUPPORTED_LOCALES: [Locale; 2] = [Locale::En, Locale::Fr];
+
+impl Locale {
+    /// A language tag, only its primary language counts, `fr-CA` is `fr`.
+    pub fn parse(tag: &str) -> Option<Locale> {
+        let language = tag.trim().split(['-', '_']).next()?;
+        SUPPORTED_LOCALES
+            .into_iter()
+            .find(|locale| locale.as_str().eq_ignore_ascii_case(language))
+    }
+
+    /// The supported language with the highest `q` in an `Accept-Language`
+    /// header, English when none is supported.
+    pub fn from_accept_language(header: &str) -> Locale {
+        let mut best: Option<(Locale, f32)> = None;
+        for entry in header.split(',') {
+            let mut parts = entry.split(';');
+            let Some(locale) = parts.next().and_then(Locale::parse) else {
+                continue;
+            };
+            let quality = parts
+                .find_map(|part| part.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok());
+            // `q=0` means "not this one", a malformed `q` is ignored
+            let Some(quality) = quality.filter(|q| *q > 0.0) else {
+                continue;
+            };
+            // on a tie the first one listed wins
+            if best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((locale, quality));
+            }
+        }
+        best.map(|(locale, _)| locale).unwrap_or_default()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Fr => "fr",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::Locale;
+
+    #[test]
+    fn the_primary_language_of_a_tag_is_used() {
+        assert_eq!(Locale::parse("fr-CA"), Some(Locale::Fr));
+        assert_eq!(Locale::parse("EN"), Some(Locale::En));
+        assert_eq!(Locale::parse("de"), None);
+    }
+
+    #[test]
+    fn the_supported_language_with_the_highest_quality_wins() {
+        assert_eq!(
+            Locale::from_accept_language("de-DE,fr;q=0.8,en;q=0.5"),
+            Locale::Fr
+        );
+        assert_eq!(
+            Locale::from_accept_language("en;q=0.4, fr-FR;q=0.9"),
+            Locale::Fr
+        );
+        assert_eq!(Locale::from_accept_language("en-US,fr"), Locale::En);
+    }
+
+    #[test]
+    fn english_is_the_fallback() {
+        assert_eq!(Locale::from_accept_language(""), Locale::En);
+        assert_eq!(Locale::from_accept_language("de,ja;q=0.5"), Locale::En);
+        assert_eq!(Locale::from_accept_language("fr;q=0"), Locale::En);
+        assert_eq!(Locale::from_accept_language("*"), Locale::En);
+    }
+}
@@ -1,7 +1,21 @@
+mod allowlist_entry;
+mod application_base_url;
+mod link_alias;
+mod link_token_status;
+mod locale;
 mod new_recipient;
+mod normalized_url;
 mod recipient_email;
 mod recipient_name;
+mod target_url;
 
+pub use allowlist_entry::AllowlistEntry;
+pub use application_base_url::ApplicationBaseUrl;
+pub use link_alias::LinkAlias;
+pub use link_token_status::LinkTokenStatus;
+pub use locale::Locale;
 pub use new_recipient::NewRecipient;
+pub use normalized_url::NormalizedUrl;
 pub use recipient_email::RecipientEmail;
 pub use recipient_name::RecipientName;
+pub use target_url::{TargetUrl, TargetUrlPolicy};
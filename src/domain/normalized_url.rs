@@ -0,0 +1,117 @@
+use reqwest::Url;
+
+/// A url in a canonical form, two urls pointing to the same resource compare
+/// equal once normalized.
+///
+/// Scheme and host are lowercased, default ports dropped, trailing slashes of
+/// the path removed and query parameters sorted.
+#[derive(Debug, PartialEq, Eq)]
+pub struct NormalizedUrl(String);
+
+impl NormalizedUrl {
+    pub fn parse(s: &str) -> Result<NormalizedUrl, String> {
+        let mut url =
+            Url::parse(s.trim()).map_err(|e| format!("{} is not a valid url, {}", s, e))?;
+
+        // `Url` already does it for http(s), but not for every scheme
+        if let Some(host) = url.host_str().map(str::to_lowercase) {
+            url.set_host(Some(&host))
+                .map_err(|e| format!("{} is not a valid url, {}", s, e))?;
+        }
+        if url.port().is_some() && url.port() == default_port(url.scheme()) {
+            let _ = url.set_port(None);
+        }
+
+        let path = url.path().trim_end_matches('/').to_string();
+        if !path.is_empty() {
+            url.set_path(&path);
+        }
+
+        let mut query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        if query.is_empty() {
+            url.set_query(None);
+        } else {
+            query.sort();
+            url.query_pairs_mut().clear().extend_pairs(query);
+        }
+
+        Ok(Self(url.to_string()))
+    }
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "http" | "ws" => Some(80),
+        "https" | "wss" => Some(443),
+        "ftp" => Some(21),
+        _ => None,
+    }
+}
+
+impl AsRef<str> for NormalizedUrl {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::normalized_url::NormalizedUrl;
+    use claims::assert_err;
+
+    fn normalize(s: &str) -> String {
+        NormalizedUrl::parse(s).unwrap().as_ref().to_owned()
+    }
+
+    #[test]
+    fn scheme_and_host_are_lowercased() {
+        assert_eq!(
+            normalize("HTTPS://Example.COM/Docs"),
+            "https://example.com/Docs"
+        );
+    }
+
+    #[test]
+    fn default_ports_are_removed() {
+        assert_eq!(
+            normalize("https://example.com:443/"),
+            "https://example.com/"
+        );
+        assert_eq!(normalize("http://example.com:80/a"), "http://example.com/a");
+        assert_eq!(
+            normalize("http://example.com:8080/a"),
+            "http://example.com:8080/a"
+        );
+    }
+
+    #[test]
+    fn trailing_slashes_are_removed() {
+        assert_eq!(
+            normalize("https://example.com/docs/"),
+            "https://example.com/docs"
+        );
+        assert_eq!(normalize("https://example.com"), "https://example.com/");
+    }
+
+    #[test]
+    fn query_parameters_are_sorted() {
+        assert_eq!(
+            normalize("https://example.com/search?q=rust&page=2&a=1"),
+            "https://example.com/search?a=1&page=2&q=rust"
+        );
+        assert_eq!(normalize("https://example.com/?"), "https://example.com/");
+    }
+
+    #[test]
+    fn equivalent_urls_are_equal_once_normalized() {
+        assert_eq!(
+            NormalizedUrl::parse("https://Example.com:443/docs/?b=2&a=1"),
+            NormalizedUrl::parse("https://example.com/docs?a=1&b=2")
+        );
+    }
+
+    #[test]
+    fn invalid_urls_are_rejected() {
+        assert_err!(NormalizedUrl::parse("not a url"));
+    }
+}
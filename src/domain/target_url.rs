@@ -0,0 +1,210 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use url::{Host, Url};
+
+const ALLOWED_SCHEMES: [&str; 2] = ["http", "https"];
+
+/// What a target url may point to.
+#[derive(Debug, Default)]
+pub struct TargetUrlPolicy {
+    /// Host of our own `base_url`, links to it would redirect in a loop.
+    own_host: Option<String>,
+    /// Blocked domains, their subdomains are blocked too.
+    blocklist: Vec<String>,
+}
+
+impl TargetUrlPolicy {
+    pub fn new(base_url: &str, blocklist: Vec<String>) -> Self {
+        let own_host = Url::parse(base_url).ok().and_then(|url| {
+            url.host_str()
+                .map(|host| host.trim_end_matches('.').to_lowercase())
+        });
+        let blocklist = blocklist
+            .into_iter()
+            .map(|domain| domain.trim().trim_start_matches('.').to_lowercase())
+            .filter(|domain| !domain.is_empty())
+            .collect();
+        Self {
+            own_host,
+            blocklist,
+        }
+    }
+
+    fn is_blocked(&self, domain: &str) -> bool {
+        self.blocklist.iter().any(|blocked| {
+            domain == blocked
+                || domain
+                    .strip_suffix(blocked.as_str())
+                    .is_some_and(|subdomain| subdomain.ends_with('.'))
+        })
+    }
+}
+
+/// A url recipients can safely be redirected to.
+///
+/// Hosts are checked as written, a public domain resolving to a private
+/// address is not caught.
+#[derive(Debug)]
+pub struct TargetUrl(String);
+
+impl TargetUrl {
+    pub fn parse(s: &str, policy: &TargetUrlPolicy) -> Result<TargetUrl, String> {
+        let url = Url::parse(s.trim()).map_err(|e| format!("{} is not a valid url, {}", s, e))?;
+
+        if !ALLOWED_SCHEMES.contains(&url.scheme()) {
+            return Err(format!(
+                "{}: urls are not allowed, use http or https",
+                url.scheme()
+            ));
+        }
+        let host = url.host().ok_or_else(|| format!("{} has no host", s))?;
+        let is_internal = match host {
+            Host::Ipv4(ip) => is_internal_ip(IpAddr::V4(ip)),
+            Host::Ipv6(ip) => is_internal_ip(IpAddr::V6(ip)),
+            Host::Domain(domain) => {
+                let domain = domain.trim_end_matches('.');
+                domain == "localhost" || domain.ends_with(".localhost")
+            }
+        };
+        if is_internal {
+            return Err(format!(
+                "{} is a private address, only public hosts are allowed",
+                host
+            ));
+        }
+
+        // `example.com.` is the same host as `example.com`
+        let host = host.to_string().trim_end_matches('.').to_lowercase();
+        if policy.own_host.as_ref() == Some(&host) {
+            return Err("links can't point back to this url shortener".to_string());
+        }
+        if policy.is_blocked(&host) {
+            return Err(format!("{} is blocked", host));
+        }
+
+        Ok(Self(url.to_string()))
+    }
+}
+
+fn is_internal_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_internal_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_internal_ipv4(ip),
+            None => is_internal_ipv6(ip),
+        },
+    }
+}
+
+fn is_internal_ipv4(ip: Ipv4Addr) -> bool {
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_multicast()
+        // "this network", 0.0.0.0/8
+        || ip.octets()[0] == 0
+        // reserved, 240.0.0.0/4, the broadcast address included
+        || ip.octets()[0] >= 240
+        // carrier grade nat, 100.64.0.0/10
+        || (ip.octets()[0] == 100 && (ip.octets()[1] & 0b1100_0000) == 64)
+}
+
+fn is_internal_ipv6(ip: Ipv6Addr) -> bool {
+    ip.is_loopback() || ip.is_unspecified() || ip.is_unique_local() || ip.is_unicast_link_local()
+}
+
+impl AsRef<str> for TargetUrl {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::target_url::{TargetUrl, TargetUrlPolicy};
+    use claims::{assert_err, assert_ok};
+
+    fn policy() -> TargetUrlPolicy {
+        TargetUrlPolicy::new(
+            "https://short.example.com",
+            vec!["evil.com".to_string(), ".phishing.net".to_string()],
+        )
+    }
+
+    #[test]
+    fn public_http_and_https_urls_are_accepted() {
+        assert_ok!(TargetUrl::parse(
+            "https://www.rust-lang.org/learn",
+            &policy()
+        ));
+        assert_ok!(TargetUrl::parse("http://93.184.216.34:8080/", &policy()));
+    }
+
+    #[test]
+    fn other_schemes_are_rejected() {
+        for url in [
+            "javascript:alert(1)",
+            "data:text/html,<h1>hi</h1>",
+            "file:///etc/passwd",
+            "ftp://example.com/file",
+        ] {
+            assert_err!(TargetUrl::parse(url, &policy()));
+        }
+    }
+
+    #[test]
+    fn private_loopback_and_link_local_hosts_are_rejected() {
+        for url in [
+            "http://127.0.0.1:5432",
+            "http://10.0.0.1",
+            "http://192.168.1.1/admin",
+            "http://172.16.0.1",
+            "http://169.254.169.254/latest/meta-data",
+            "http://0.0.0.0",
+            "http://0.1.2.3",
+            "http://224.0.0.1",
+            "http://239.255.255.250",
+            "http://240.0.0.1",
+            "http://255.255.255.255",
+            "http://100.64.0.1",
+            "http://[::1]/",
+            "http://[fe80::1]/",
+            "http://[fd00::1]/",
+            "http://[::ffff:127.0.0.1]/",
+            "http://localhost:8080",
+            "http://api.localhost",
+        ] {
+            assert_err!(TargetUrl::parse(url, &policy()), "{} was accepted", url);
+        }
+    }
+
+    #[test]
+    fn our_own_base_url_is_rejected() {
+        assert_err!(TargetUrl::parse("https://short.example.com/abc", &policy()));
+        assert_err!(TargetUrl::parse(
+            "http://SHORT.example.com:8080/",
+            &policy()
+        ));
+    }
+
+    #[test]
+    fn our_own_base_url_with_a_trailing_dot_is_rejected() {
+        assert_err!(TargetUrl::parse("https://short.example.com./x", &policy()));
+        let policy = TargetUrlPolicy::new("https://short.example.com.", vec![]);
+        assert_err!(TargetUrl::parse("https://short.example.com/x", &policy));
+    }
+
+    #[test]
+    fn blocked_domains_and_their_subdomains_are_rejected() {
+        assert_err!(TargetUrl::parse("https://evil.com", &policy()));
+        assert_err!(TargetUrl::parse("https://login.evil.com/", &policy()));
+        assert_err!(TargetUrl::parse("https://phishing.net/", &policy()));
+        assert_ok!(TargetUrl::parse("https://notevil.com/", &policy()));
+    }
+
+    #[test]
+    fn the_reason_is_reported() {
+        let reason = TargetUrl::parse("file:///etc/passwd", &policy()).unwrap_err();
+        assert!(reason.contains("file"));
+    }
+}
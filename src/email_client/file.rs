@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use lettre::{AsyncFileTransport, AsyncTransport, Tokio1Executor};
+
+use crate::email_client::{Email, EmailError, EmailTransport};
+
+/// Writes every email to an `.eml` file in a directory instead of sending
+/// it, for local development without an email account.
+pub struct FileTransport {
+    directory: PathBuf,
+    transport: AsyncFileTransport<Tokio1Executor>,
+}
+
+impl FileTransport {
+    /// The directory is created if it doesn't exist yet.
+    pub fn new(directory: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self {
+            transport: AsyncFileTransport::new(&directory),
+            directory,
+        })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for FileTransport {
+    async fn send(&self, email: &Email<'_>) -> Result<(), EmailError> {
+        let id = self.transport.send(email.to_message()?).await?;
+        tracing::info!(
+            "Wrote the email to {}",
+            self.directory.join(format!("{}.eml", id)).display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{
+        domain::RecipientEmail,
+        email_client::{EmailClient, FileTransport},
+    };
+
+    #[tokio::test]
+    async fn emails_are_written_to_eml_files() {
+        // Arrange
+        let directory = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let email_client = EmailClient::new(
+            RecipientEmail::parse("sender@example.com".to_string()).unwrap(),
+            FileTransport::new(&directory).unwrap(),
+        );
+
+        // Act
+        email_client
+            .send_email(
+                RecipientEmail::parse("hamada@yahoo.com".to_string()).unwrap(),
+                "A friend wants to show you something!",
+                "<p>html body</p>",
+                "plain body",
+            )
+            .await
+            .unwrap();
+
+        // Assert
+        let files: Vec<_> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), "eml");
+        let eml = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(eml.contains("To: hamada@yahoo.com"));
+        assert!(eml.contains("Subject: A friend wants to show you something!"));
+        assert!(eml.contains("plain body"));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,88 @@
+mod file;
+mod postmark;
+mod smtp;
+
+use async_trait::async_trait;
+use lettre::{Message, message::MultiPart};
+
+use crate::domain::RecipientEmail;
+
+pub use file::FileTransport;
+pub use postmark::PostmarkTransport;
+pub use smtp::SmtpTransport;
+
+/// An email ready to be handed to a transport.
+pub struct Email<'a> {
+    pub from: &'a RecipientEmail,
+    pub to: &'a RecipientEmail,
+    pub subject: &'a str,
+    pub html_body: &'a str,
+    pub text_body: &'a str,
+}
+
+impl Email<'_> {
+    /// The MIME message, with both the plain text and the html body.
+    pub fn to_message(&self) -> Result<Message, EmailError> {
+        let message = Message::builder()
+            .from(self.from.as_ref().parse()?)
+            .to(self.to.as_ref().parse()?)
+            .subject(self.subject)
+            .multipart(MultiPart::alternative_plain_html(
+                self.text_body.to_string(),
+                self.html_body.to_string(),
+            ))?;
+        Ok(message)
+    }
+}
+
+/// How emails leave the application, chosen by `EmailTransportSettings`.
+#[async_trait]
+pub trait EmailTransport: Send + Sync {
+    async fn send(&self, email: &Email<'_>) -> Result<(), EmailError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum EmailError {
+    #[error("couldn't reach the email api, {0}")]
+    Postmark(#[from] reqwest::Error),
+    #[error("invalid email address, {0}")]
+    Address(#[from] lettre::address::AddressError),
+    #[error("couldn't build the email, {0}")]
+    Message(#[from] lettre::error::Error),
+    #[error("couldn't send the email over smtp, {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error("couldn't write the email file, {0}")]
+    File(#[from] lettre::transport::file::Error),
+}
+
+/// Sends emails from the configured sender through a transport.
+pub struct EmailClient {
+    sender: RecipientEmail,
+    transport: Box<dyn EmailTransport>,
+}
+
+impl EmailClient {
+    pub fn new(sender: RecipientEmail, transport: impl EmailTransport + 'static) -> Self {
+        Self {
+            sender,
+            transport: Box::new(transport),
+        }
+    }
+
+    pub async fn send_email(
+        &self,
+        recipient: RecipientEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<(), EmailError> {
+        let email = Email {
+            from: &self.sender,
+            to: &recipient,
+            subject,
+            html_body: html_content,
+            text_body: text_content,
+        };
+        self.transport.send(&email).await
+    }
+}
@@ -1,13 +1,15 @@
+use async_trait::async_trait;
 use reqwest::{Client, Url};
 use secrecy::{ExposeSecret, SecretString};
 use serde::Serialize;
 
-use crate::domain::RecipientEmail;
+use crate::email_client::{Email, EmailError, EmailTransport};
 
-pub struct EmailClient {
+/// Postmark's email api.
+pub struct PostmarkTransport {
     http_client: reqwest::Client,
-    sender: RecipientEmail,
-    base_url: String,
+    /// The `/email` endpoint under the configured base url.
+    url: Url,
     authorization_token: SecretString,
 }
 
@@ -21,41 +23,35 @@ struct SendEmailRequest<'a> {
     text_body: &'a str,
 }
 
-impl EmailClient {
+impl PostmarkTransport {
+    /// The url is checked here, at startup, the outbox worker can't do
+    /// anything about a wrong one.
     pub fn new(
-        sender: RecipientEmail,
-        base_url: String,
+        base_url: &str,
         authorization_token: SecretString,
         timeout: std::time::Duration,
-    ) -> Self {
-        Self {
+    ) -> Result<Self, url::ParseError> {
+        let url = Url::parse(base_url)?.join("email")?;
+        Ok(Self {
             http_client: Client::builder().timeout(timeout).build().unwrap(),
-            sender,
-            base_url,
+            url,
             authorization_token,
-        }
+        })
     }
+}
 
-    pub async fn send_email(
-        &self,
-        recipient: RecipientEmail,
-        subject: &str,
-        html_content: &str,
-        text_content: &str,
-    ) -> Result<(), reqwest::Error> {
-        let base = Url::parse(&self.base_url).expect("url from config is wrong");
-        let url = base
-            .join("email")
-            .expect("can't append email to email url from config");
+#[async_trait]
+impl EmailTransport for PostmarkTransport {
+    async fn send(&self, email: &Email<'_>) -> Result<(), EmailError> {
         let request_body = SendEmailRequest {
-            from: self.sender.as_ref(),
-            to: recipient.as_ref(),
-            subject,
-            html_body: html_content,
-            text_body: text_content,
+            from: email.from.as_ref(),
+            to: email.to.as_ref(),
+            subject: email.subject,
+            html_body: email.html_body,
+            text_body: email.text_body,
         };
         self.http_client
-            .post(url)
+            .post(self.url.clone())
             .header(
                 "X-Postmark-Server-Token",
                 self.authorization_token.expose_secret(),
@@ -84,7 +80,10 @@ mod tests {
         matchers::{any, header, header_exists, method, path},
     };
 
-    use crate::{domain::RecipientEmail, email_client::EmailClient};
+    use crate::{
+        domain::RecipientEmail,
+        email_client::{EmailClient, PostmarkTransport},
+    };
 
     /// Generate a random email subject
     fn subject() -> String {
@@ -101,13 +100,16 @@ mod tests {
         RecipientEmail::parse(SafeEmail().fake()).unwrap()
     }
 
-    /// Get a test instance of `EmailClient`.
+    /// Get a test instance of `EmailClient` sending through Postmark.
     fn email_client(base_url: String) -> EmailClient {
         EmailClient::new(
             email(),
-            base_url,
-            SecretString::from(Faker.fake::<String>()),
-            std::time::Duration::from_millis(200),
+            PostmarkTransport::new(
+                &base_url,
+                SecretString::from(Faker.fake::<String>()),
+                std::time::Duration::from_millis(200),
+            )
+            .unwrap(),
         )
     }
 
@@ -117,21 +119,27 @@ mod tests {
         fn matches(&self, request: &wiremock::Request) -> bool {
             match request.body_json::<serde_json::Value>() {
                 Ok(body) => {
-                    if body.get("From").is_some()
+                    body.get("From").is_some()
                         && body.get("To").is_some()
                         && body.get("Subject").is_some()
                         && body.get("HtmlBody").is_some()
                         && body.get("TextBody").is_some()
-                    {
-                        return true;
-                    }
-                    return false;
                 }
-                Err(_) => return false,
-            };
+                Err(_) => false,
+            }
         }
     }
 
+    #[test]
+    fn an_invalid_base_url_is_refused_upfront() {
+        let transport = PostmarkTransport::new(
+            "localhost",
+            SecretString::from(Faker.fake::<String>()),
+            std::time::Duration::from_millis(200),
+        );
+        assert!(transport.is_err());
+    }
+
     #[tokio::test]
     async fn send_email_sends_the_expected_request() {
         // Arrange
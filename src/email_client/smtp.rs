@@ -0,0 +1,45 @@
+use async_trait::async_trait;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
+    transport::smtp::authentication::Credentials,
+};
+use secrecy::{ExposeSecret, SecretString};
+
+use crate::email_client::{Email, EmailError, EmailTransport};
+
+/// Any smtp server, the connection is always upgraded with STARTTLS.
+pub struct SmtpTransport {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    /// Credentials are only sent when both the username and the password are
+    /// set.
+    pub fn new(
+        host: &str,
+        port: u16,
+        credentials: Option<(String, SecretString)>,
+        timeout: std::time::Duration,
+    ) -> Result<Self, EmailError> {
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+            .port(port)
+            .timeout(Some(timeout));
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(
+                username,
+                password.expose_secret().to_string(),
+            ));
+        }
+        Ok(Self {
+            transport: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, email: &Email<'_>) -> Result<(), EmailError> {
+        self.transport.send(email.to_message()?).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,205 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    configuration::EmailOutboxSettings, domain::RecipientEmail, email_client::EmailClient,
+};
+
+/// Queue an email in the transaction of whatever it is about, it's only sent
+/// once that commits.
+#[tracing::instrument(
+    name = "Queue an email in the outbox",
+    skip(transaction, recipient, html_body, text_body)
+)]
+pub async fn enqueue_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    recipient: &RecipientEmail,
+    subject: &str,
+    html_body: &str,
+    text_body: &str,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+    let query = sqlx::query!(
+        r#"
+    INSERT INTO email_outbox (id, recipient, subject, html_body, text_body, next_attempt_at,
+        created_at)
+    VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        Uuid::new_v4(),
+        recipient.as_ref(),
+        subject,
+        html_body,
+        text_body,
+        now,
+        now
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+pub enum ExecutionOutcome {
+    EmailDelivered,
+    EmailFailed,
+    EmptyQueue,
+}
+
+/// Deliver queued emails until the application stops, failed deliveries are
+/// retried with an exponential backoff.
+pub async fn run_worker_until_stopped(
+    pool: PgPool,
+    email_client: EmailClient,
+    settings: EmailOutboxSettings,
+) {
+    loop {
+        match try_deliver_next(&pool, &email_client, &settings).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(settings.poll_interval()).await;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::error!("couldn't go through the email outbox, {}", e);
+                tokio::time::sleep(settings.poll_interval()).await;
+            }
+        }
+    }
+}
+
+/// Try to deliver the oldest email that is due, several workers can run at
+/// once as each one skips the emails locked by the others.
+#[tracing::instrument(
+    skip_all,
+    fields(email_id = tracing::field::Empty, attempts = tracing::field::Empty),
+    err
+)]
+pub async fn try_deliver_next(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    settings: &EmailOutboxSettings,
+) -> Result<ExecutionOutcome, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let Some(email) = sqlx::query!(
+        r#"
+    SELECT id, recipient, subject, html_body, text_body, attempts
+    FROM email_outbox
+    WHERE status = 'pending' AND next_attempt_at <= now()
+    ORDER BY next_attempt_at
+    FOR UPDATE
+    SKIP LOCKED
+    LIMIT 1
+        "#
+    )
+    .fetch_optional(&mut *transaction)
+    .await?
+    else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    let attempts = email.attempts + 1;
+    tracing::Span::current()
+        .record("email_id", tracing::field::display(email.id))
+        .record("attempts", attempts);
+
+    let delivery = match RecipientEmail::parse(email.recipient) {
+        Ok(recipient) => email_client
+            .send_email(
+                recipient,
+                &email.subject,
+                &email.html_body,
+                &email.text_body,
+            )
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+
+    let outcome = match delivery {
+        Ok(()) => {
+            sqlx::query!(
+                r#"
+    UPDATE email_outbox SET status = 'sent', attempts = $1, sent_at = now()
+    WHERE id = $2
+                "#,
+                attempts,
+                email.id
+            )
+            .execute(&mut *transaction)
+            .await?;
+            ExecutionOutcome::EmailDelivered
+        }
+        Err(e) if attempts >= settings.max_attempts => {
+            tracing::error!("giving up on the email after {} attempts, {}", attempts, e);
+            sqlx::query!(
+                r#"
+    UPDATE email_outbox SET status = 'failed', attempts = $1, last_error = $2
+    WHERE id = $3
+                "#,
+                attempts,
+                e,
+                email.id
+            )
+            .execute(&mut *transaction)
+            .await?;
+            ExecutionOutcome::EmailFailed
+        }
+        Err(e) => {
+            tracing::warn!("couldn't deliver the email, retrying later, {}", e);
+            let next_attempt_at = Utc::now() + retry_delay(attempts, settings);
+            sqlx::query!(
+                r#"
+    UPDATE email_outbox SET attempts = $1, last_error = $2, next_attempt_at = $3
+    WHERE id = $4
+                "#,
+                attempts,
+                e,
+                next_attempt_at,
+                email.id
+            )
+            .execute(&mut *transaction)
+            .await?;
+            ExecutionOutcome::EmailFailed
+        }
+    };
+    transaction.commit().await?;
+    Ok(outcome)
+}
+
+/// The base delay doubled for every failed attempt but the first, up to the
+/// maximum delay.
+pub fn retry_delay(attempts: i32, settings: &EmailOutboxSettings) -> Duration {
+    let doublings = attempts.saturating_sub(1).clamp(0, 31) as u32;
+    settings
+        .retry_base_delay()
+        .saturating_mul(2u32.saturating_pow(doublings))
+        .min(settings.retry_max_delay())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{configuration::EmailOutboxSettings, email_outbox::retry_delay};
+
+    fn settings() -> EmailOutboxSettings {
+        EmailOutboxSettings {
+            poll_interval_milliseconds: 1000,
+            max_attempts: 10,
+            retry_base_delay_milliseconds: 1000,
+            retry_max_delay_milliseconds: 60_000,
+        }
+    }
+
+    #[test]
+    fn the_retry_delay_doubles_after_every_attempt() {
+        assert_eq!(retry_delay(1, &settings()), Duration::from_secs(1));
+        assert_eq!(retry_delay(2, &settings()), Duration::from_secs(2));
+        assert_eq!(retry_delay(5, &settings()), Duration::from_secs(16));
+    }
+
+    #[test]
+    fn the_retry_delay_is_capped() {
+        assert_eq!(retry_delay(7, &settings()), Duration::from_secs(60));
+        assert_eq!(retry_delay(i32::MAX, &settings()), Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,181 @@
+use rand::{Rng, seq::IndexedRandom};
+use sqlx::PgConnection;
+
+use crate::{configuration::IdGeneratorSettings, domain::LinkAlias};
+
+const BASE62: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Counter ids are shuffled within `0..2^COUNTER_BITS`, about 7 base62
+/// characters.
+const COUNTER_BITS: u32 = 36;
+const COUNTER_MASK: u64 = (1 << COUNTER_BITS) - 1;
+// any odd number is invertible modulo a power of two, so no two counters
+// end up with the same id
+const COUNTER_MULTIPLIER: u64 = 0x9E37_79B9;
+
+const ADJECTIVES: [&str; 32] = [
+    "amber", "bold", "brave", "bright", "calm", "clever", "cosy", "crisp", "daring", "eager",
+    "fancy", "gentle", "golden", "happy", "humble", "jolly", "keen", "lively", "lucky", "mellow",
+    "misty", "noble", "proud", "quick", "quiet", "rapid", "shiny", "silent", "sunny", "swift",
+    "tidy", "witty",
+];
+const NOUNS: [&str; 32] = [
+    "badger", "beacon", "canyon", "cedar", "comet", "falcon", "fern", "harbor", "heron", "island",
+    "lagoon", "lantern", "maple", "meadow", "otter", "panda", "pebble", "pine", "puffin", "quartz",
+    "raven", "river", "robin", "saffron", "summit", "thistle", "tiger", "tulip", "valley",
+    "walrus", "willow", "zephyr",
+];
+
+/// How new short link ids are made, chosen by `IdGeneratorSettings`.
+///
+/// Ids are only candidates, `save_new_link` retries with a new one when an id
+/// is already taken.
+#[derive(Debug, Clone)]
+pub enum IdGenerator {
+    /// Random base62 characters.
+    Random { length: usize },
+    /// The `link_id_counter` sequence, shuffled with `key` so consecutive links
+    /// don't get guessable ids.
+    Counter { key: u64 },
+    /// Dash separated words, e.g. `swift-otter` for 2 words.
+    Words { count: usize },
+}
+
+impl IdGenerator {
+    pub fn new(settings: &IdGeneratorSettings) -> Result<Self, String> {
+        match *settings {
+            IdGeneratorSettings::Random { length: 0 } => {
+                Err("random ids need a length of at least 1".to_string())
+            }
+            IdGeneratorSettings::Words { count: 0 } => {
+                Err("word ids need a count of at least 1".to_string())
+            }
+            IdGeneratorSettings::Random { length } => Ok(IdGenerator::Random { length }),
+            IdGeneratorSettings::Counter { key } => Ok(IdGenerator::Counter { key }),
+            IdGeneratorSettings::Words { count } => {
+                if count == 1 {
+                    tracing::warn!(
+                        "word ids with a count of 1 only make {} different ids",
+                        NOUNS.len()
+                    );
+                }
+                Ok(IdGenerator::Words { count })
+            }
+        }
+    }
+
+    /// Ids shadowed by a path of the application are skipped.
+    #[tracing::instrument(name = "Generate a link id", skip(connection))]
+    pub async fn generate(&self, connection: &mut PgConnection) -> Result<String, sqlx::Error> {
+        loop {
+            let id = self.candidate(connection).await?;
+            if !LinkAlias::is_reserved(&id) {
+                return Ok(id);
+            }
+        }
+    }
+
+    async fn candidate(&self, connection: &mut PgConnection) -> Result<String, sqlx::Error> {
+        let id = match self {
+            IdGenerator::Random { length } => random_base62(&mut rand::rng(), *length),
+            IdGenerator::Counter { key } => {
+                let counter = sqlx::query!(r#"SELECT nextval('link_id_counter') AS "counter!""#)
+                    .fetch_one(connection)
+                    .await?
+                    .counter;
+                encode_base62(obfuscate(counter as u64, *key))
+            }
+            IdGenerator::Words { count } => random_words(&mut rand::rng(), *count),
+        };
+        Ok(id)
+    }
+}
+
+pub fn random_base62(rng: &mut impl Rng, length: usize) -> String {
+    (0..length)
+        .map(|_| char::from(BASE62[rng.random_range(0..BASE62.len())]))
+        .collect()
+}
+
+/// A bijection of `0..2^36`, counters past it wrap around.
+pub fn obfuscate(counter: u64, key: u64) -> u64 {
+    (counter.wrapping_mul(COUNTER_MULTIPLIER) & COUNTER_MASK) ^ (key & COUNTER_MASK)
+}
+
+pub fn encode_base62(mut n: u64) -> String {
+    let mut encoded = Vec::new();
+    loop {
+        encoded.push(BASE62[(n % 62) as usize]);
+        n /= 62;
+        if n == 0 {
+            break;
+        }
+    }
+    encoded.reverse();
+    String::from_utf8(encoded).unwrap()
+}
+
+/// Adjectives followed by a noun, a single word is just a noun.
+pub fn random_words(rng: &mut impl Rng, count: usize) -> String {
+    let mut words: Vec<&str> = (1..count)
+        .map(|_| *ADJECTIVES.choose(rng).unwrap())
+        .collect();
+    words.push(NOUNS.choose(rng).unwrap());
+    words.join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use claims::assert_err;
+
+    use crate::{
+        configuration::IdGeneratorSettings,
+        id_generator::{IdGenerator, encode_base62, obfuscate, random_base62, random_words},
+    };
+
+    #[test]
+    fn generators_that_make_empty_ids_are_rejected() {
+        assert_err!(IdGenerator::new(&IdGeneratorSettings::Random { length: 0 }));
+        assert_err!(IdGenerator::new(&IdGeneratorSettings::Words { count: 0 }));
+    }
+
+    #[test]
+    fn random_ids_have_the_configured_length_and_are_base62() {
+        let mut rng = rand::rng();
+        for length in [1, 7, 12] {
+            let id = random_base62(&mut rng, length);
+            assert_eq!(id.len(), length);
+            assert!(id.chars().all(|c| c.is_ascii_alphanumeric()));
+        }
+    }
+
+    #[test]
+    fn obfuscated_counters_never_collide() {
+        let ids: HashSet<_> = (0..100_000)
+            .map(|counter| encode_base62(obfuscate(counter, 42)))
+            .collect();
+        assert_eq!(ids.len(), 100_000);
+    }
+
+    #[test]
+    fn consecutive_counters_are_not_consecutive_ids() {
+        assert!(obfuscate(2, 0).abs_diff(obfuscate(1, 0)) > 1);
+        assert_ne!(obfuscate(1, 0), obfuscate(1, 1));
+    }
+
+    #[test]
+    fn base62_encoding_matches_known_values() {
+        assert_eq!(encode_base62(0), "0");
+        assert_eq!(encode_base62(61), "z");
+        assert_eq!(encode_base62(62), "10");
+    }
+
+    #[test]
+    fn word_ids_have_the_configured_number_of_words() {
+        let mut rng = rand::rng();
+        assert_eq!(random_words(&mut rng, 1).split('-').count(), 1);
+        assert_eq!(random_words(&mut rng, 3).split('-').count(), 3);
+    }
+}
@@ -1,6 +1,8 @@
 pub mod configuration;
 pub mod domain;
 pub mod email_client;
+pub mod email_outbox;
+pub mod id_generator;
 pub mod routes;
 pub mod startup;
 pub mod telemetry;
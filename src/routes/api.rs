@@ -0,0 +1,337 @@
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Path, State, rejection::JsonRejection},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    domain::{LinkTokenStatus, NewRecipient},
+    routes::{
+        ApiKey, Confirmation, ConfirmationError, FormData, LinkError, LinkTarget, RecipientError,
+        RecipientRow, Registration, SCOPE_LINKS_CREATE, SCOPE_RECIPIENTS_READ, authorize_owner,
+        confirm_link_token, create_api_key, get_available_link, get_link_owner,
+        get_link_recipients, register_recipient, save_new_link,
+    },
+    startup::AppState,
+};
+
+/// Header carrying the management token on owner only endpoints.
+pub const MANAGEMENT_TOKEN_HEADER: &str = "X-Management-Token";
+
+/// The json counterpart of the html endpoints, mounted under `/api/v1`.
+pub fn api_v1_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/links", post(create_link_json))
+        .route("/links/{id}", get(get_link_metadata))
+        .route(
+            "/links/{id}/recipients",
+            post(register_recipient_json).get(list_recipients),
+        )
+        .route("/confirmations", post(confirm_json))
+        .route("/api_keys", post(create_api_key))
+}
+
+#[derive(Serialize)]
+pub struct CreatedLink {
+    pub id: String,
+    pub short_url: String,
+    /// Only present when a new owner was created along with the link.
+    pub management_token: Option<String>,
+}
+
+#[tracing::instrument(name = "Creating a new link from the api", skip(app_state, body))]
+pub async fn create_link_json(
+    State(app_state): State<Arc<AppState>>,
+    api_key: Option<ApiKey>,
+    body: Result<Json<LinkTarget>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let Some(api_key) = &api_key {
+        api_key.require_scope(SCOPE_LINKS_CREATE)?;
+    }
+    let Json(new_link) = body?;
+    let new_link = save_new_link(&app_state, new_link, api_key.as_ref()).await?;
+    let short_url = app_state.base_url.short_link(&new_link.id).to_string();
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedLink {
+            id: new_link.id,
+            short_url,
+            management_token: new_link.management_token,
+        }),
+    ))
+}
+
+/// What anyone can know about a link, the target url stays hidden until a
+/// recipient is verified.
+#[derive(Serialize)]
+pub struct LinkMetadata {
+    pub id: String,
+    pub requires_approval: bool,
+    pub active_from: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_clicks: Option<i32>,
+    pub max_recipients: Option<i32>,
+    pub click_count: i32,
+}
+
+#[tracing::instrument(name = "Get the metadata of a link", skip(app_state))]
+pub async fn get_link_metadata(
+    State(app_state): State<Arc<AppState>>,
+    Path(link_id): Path<String>,
+) -> Result<Json<LinkMetadata>, ApiError> {
+    let link = get_available_link(&app_state.pool, &link_id).await?;
+    Ok(Json(LinkMetadata {
+        id: link.id,
+        requires_approval: link.requires_approval,
+        active_from: link.active_from,
+        expires_at: link.expires_at,
+        max_clicks: link.max_clicks,
+        max_recipients: link.max_recipients,
+        click_count: link.click_count,
+    }))
+}
+
+#[tracing::instrument(
+    name = "Register a recipient from the api",
+    skip(app_state, headers, body)
+)]
+pub async fn register_recipient_json(
+    State(app_state): State<Arc<AppState>>,
+    Path(link_id): Path<String>,
+    headers: HeaderMap,
+    body: Result<Json<FormData>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Json(form) = body?;
+    let new_recipient: NewRecipient = form.try_into().map_err(RecipientError::InvalidRecipient)?;
+    let status = match register_recipient(&app_state, link_id, new_recipient, &headers).await? {
+        Registration::EmailSent => "email_sent",
+        Registration::AlreadyConfirmed => "confirmed",
+        Registration::AwaitingApproval => "awaiting_approval",
+        Registration::Denied => "denied",
+    };
+    Ok((StatusCode::ACCEPTED, Json(json!({ "status": status }))))
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmationBody {
+    link_token: String,
+}
+
+#[derive(Serialize)]
+pub struct ConfirmationResponse {
+    pub link_id: String,
+    pub status: LinkTokenStatus,
+    /// Set when the link token was used before, nothing was changed.
+    pub already_confirmed: bool,
+}
+
+#[tracing::instrument(
+    name = "Confirm a link token from the api",
+    skip(app_state, headers, body)
+)]
+pub async fn confirm_json(
+    State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Result<Json<ConfirmationBody>, JsonRejection>,
+) -> Result<Json<ConfirmationResponse>, ApiError> {
+    let Json(body) = body?;
+    let confirmed_token = confirm_link_token(&app_state, &body.link_token, &headers).await?;
+    let (status, already_confirmed) = match confirmed_token.outcome {
+        Confirmation::Confirmed(status) => (status, false),
+        Confirmation::AlreadyUsed(status) => (status, true),
+        Confirmation::Expired => {
+            return Err(ApiError::new(
+                StatusCode::GONE,
+                "link_token_expired",
+                "the link token has expired, register again to get a new one",
+            ));
+        }
+        Confirmation::LimitReached => return Err(LinkError::LimitReached.into()),
+    };
+    Ok(Json(ConfirmationResponse {
+        link_id: confirmed_token.link_id,
+        status,
+        already_confirmed,
+    }))
+}
+
+/// Either the management token or an api key of the owner of the link with
+/// the `recipients:read` scope is needed.
+#[tracing::instrument(
+    name = "List the recipients of a link",
+    skip(app_state, api_key, headers)
+)]
+pub async fn list_recipients(
+    State(app_state): State<Arc<AppState>>,
+    Path(link_id): Path<String>,
+    api_key: Option<ApiKey>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<RecipientRow>>, ApiError> {
+    match api_key {
+        Some(api_key) => {
+            api_key.require_scope(SCOPE_RECIPIENTS_READ)?;
+            let owner_id = get_link_owner(&app_state.pool, &link_id)
+                .await
+                .map_err(LinkError::SqlxError)?;
+            if owner_id != Some(api_key.owner_id) {
+                return Err(LinkError::Unauthorized.into());
+            }
+        }
+        None => {
+            let management_token = headers
+                .get(MANAGEMENT_TOKEN_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .ok_or(LinkError::Unauthorized)?;
+            authorize_owner(&app_state.pool, &link_id, management_token).await?;
+        }
+    }
+    let recipients = get_link_recipients(&app_state.pool, &link_id)
+        .await
+        .map_err(LinkError::SqlxError)?;
+    Ok(Json(recipients))
+}
+
+/// Errors of the json api, rendered as
+/// `{"error": {"code": "...", "message": "..."}}`.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn internal(e: impl std::fmt::Display) -> Self {
+        tracing::error!("{}", e);
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            "something went wrong on our side",
+        )
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = json!({ "error": { "code": self.code, "message": self.message } });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::new(rejection.status(), "invalid_body", rejection.body_text())
+    }
+}
+
+impl From<LinkError> for ApiError {
+    fn from(e: LinkError) -> Self {
+        let message = e.to_string();
+        match e {
+            LinkError::GenerateUniqueId | LinkError::SqlxError(_) => ApiError::internal(e),
+            LinkError::InvalidUrl(_) => {
+                ApiError::new(StatusCode::BAD_REQUEST, "invalid_url", message)
+            }
+            LinkError::LinkNotFound => {
+                ApiError::new(StatusCode::NOT_FOUND, "link_not_found", message)
+            }
+            LinkError::LinkDisabled => ApiError::new(StatusCode::GONE, "link_disabled", message),
+            LinkError::Unauthorized => {
+                ApiError::new(StatusCode::UNAUTHORIZED, "unauthorized", message)
+            }
+            LinkError::InvalidAllowlist(_) => {
+                ApiError::new(StatusCode::BAD_REQUEST, "invalid_allowlist", message)
+            }
+            LinkError::InvalidOwnerEmail(_) => {
+                ApiError::new(StatusCode::BAD_REQUEST, "invalid_owner_email", message)
+            }
+            LinkError::MissingOwnerEmail => {
+                ApiError::new(StatusCode::BAD_REQUEST, "missing_owner_email", message)
+            }
+            LinkError::InvalidValidity(_) => {
+                ApiError::new(StatusCode::BAD_REQUEST, "invalid_validity", message)
+            }
+            LinkError::InvalidDate(_) => {
+                ApiError::new(StatusCode::BAD_REQUEST, "invalid_date", message)
+            }
+            LinkError::LinkNotYetActive(_) => {
+                ApiError::new(StatusCode::FORBIDDEN, "link_not_yet_active", message)
+            }
+            LinkError::LinkExpired => ApiError::new(StatusCode::GONE, "link_expired", message),
+            LinkError::InvalidLimit(_) => {
+                ApiError::new(StatusCode::BAD_REQUEST, "invalid_limit", message)
+            }
+            LinkError::LimitReached => {
+                ApiError::new(StatusCode::FORBIDDEN, "limit_reached", message)
+            }
+            LinkError::InvalidAlias(_) => {
+                ApiError::new(StatusCode::BAD_REQUEST, "invalid_alias", message)
+            }
+            LinkError::AliasTaken(_) => ApiError::new(StatusCode::CONFLICT, "alias_taken", message),
+            LinkError::InvalidPreview(_) => {
+                ApiError::new(StatusCode::BAD_REQUEST, "invalid_preview", message)
+            }
+        }
+    }
+}
+
+impl From<RecipientError> for ApiError {
+    fn from(e: RecipientError) -> Self {
+        let message = e.to_string();
+        match e {
+            RecipientError::SqlxError(_) => ApiError::internal(e),
+            RecipientError::InvalidRecipient(_) => {
+                ApiError::new(StatusCode::BAD_REQUEST, "invalid_recipient", message)
+            }
+            RecipientError::DuplicateEmail => {
+                ApiError::new(StatusCode::CONFLICT, "duplicate_email", message)
+            }
+            RecipientError::NotAllowed(_) => {
+                ApiError::new(StatusCode::FORBIDDEN, "recipient_not_allowed", message)
+            }
+            RecipientError::MagicLinkRequired => {
+                ApiError::new(StatusCode::FORBIDDEN, "magic_link_required", message)
+            }
+            RecipientError::NothingToResend => {
+                ApiError::new(StatusCode::NOT_FOUND, "nothing_to_resend", message)
+            }
+            RecipientError::ResendCooldown(_) => {
+                ApiError::new(StatusCode::TOO_MANY_REQUESTS, "resend_cooldown", message)
+            }
+            RecipientError::LinkError(e) => e.into(),
+        }
+    }
+}
+
+impl From<ConfirmationError> for ApiError {
+    fn from(e: ConfirmationError) -> Self {
+        match e {
+            ConfirmationError::MalformedToken => ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "malformed_link_token",
+                e.to_string(),
+            ),
+            ConfirmationError::UnknownToken => {
+                ApiError::new(StatusCode::NOT_FOUND, "unknown_link_token", e.to_string())
+            }
+            ConfirmationError::SqlxError(_) | ConfirmationError::ApprovalError(_) => {
+                ApiError::internal(e)
+            }
+        }
+    }
+}
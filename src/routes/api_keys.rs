@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{FromRequestParts, OptionalFromRequestParts, State, rejection::JsonRejection},
+    http::{HeaderMap, StatusCode, header, request::Parts},
+    response::IntoResponse,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    routes::{
+        ApiError, LinkError, MANAGEMENT_TOKEN_HEADER, generate_token, get_owner_by_token,
+        hash_token,
+    },
+    startup::AppState,
+};
+
+/// Allows creating links on behalf of the owner of the key.
+pub const SCOPE_LINKS_CREATE: &str = "links:create";
+/// Allows listing the recipients of the links of the owner of the key.
+pub const SCOPE_RECIPIENTS_READ: &str = "recipients:read";
+const SCOPES: [&str; 2] = [SCOPE_LINKS_CREATE, SCOPE_RECIPIENTS_READ];
+
+/// An api key authenticated from an `Authorization: Bearer <key>` header.
+#[derive(Debug)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+impl ApiKey {
+    pub fn require_scope(&self, scope: &'static str) -> Result<(), ApiKeyError> {
+        if self.scopes.iter().any(|granted| granted == scope) {
+            Ok(())
+        } else {
+            Err(ApiKeyError::MissingScope(scope))
+        }
+    }
+}
+
+impl FromRequestParts<Arc<AppState>> for ApiKey {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let key = bearer_token(&parts.headers).ok_or(ApiKeyError::MissingKey)?;
+        let api_key = get_api_key(&state.pool, key)
+            .await
+            .map_err(LinkError::SqlxError)?
+            .ok_or(ApiKeyError::InvalidKey)?;
+        Ok(api_key)
+    }
+}
+
+/// No `Authorization` header means no api key, a header with an unknown key
+/// is still rejected.
+impl OptionalFromRequestParts<Arc<AppState>> for ApiKey {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        if !parts.headers.contains_key(header::AUTHORIZATION) {
+            return Ok(None);
+        }
+        <Self as FromRequestParts<_>>::from_request_parts(parts, state)
+            .await
+            .map(Some)
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ApiKeyError {
+    #[error("missing `Authorization: Bearer` api key")]
+    MissingKey,
+    #[error("invalid api key")]
+    InvalidKey,
+    #[error("the api key is missing the {0} scope")]
+    MissingScope(&'static str),
+    #[error("{0} is not a valid scope")]
+    InvalidScope(String),
+    #[error("api keys need a name")]
+    MissingName,
+}
+
+impl From<ApiKeyError> for ApiError {
+    fn from(e: ApiKeyError) -> Self {
+        let message = e.to_string();
+        match e {
+            ApiKeyError::MissingKey | ApiKeyError::InvalidKey => {
+                ApiError::new(StatusCode::UNAUTHORIZED, "invalid_api_key", message)
+            }
+            ApiKeyError::MissingScope(_) => {
+                ApiError::new(StatusCode::FORBIDDEN, "insufficient_scope", message)
+            }
+            ApiKeyError::InvalidScope(_) => {
+                ApiError::new(StatusCode::BAD_REQUEST, "invalid_scope", message)
+            }
+            ApiKeyError::MissingName => {
+                ApiError::new(StatusCode::BAD_REQUEST, "missing_name", message)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NewApiKey {
+    name: String,
+    scopes: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct CreatedApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// The one and only time the key is shown.
+    pub key: String,
+}
+
+/// Owners create api keys with their management token.
+#[tracing::instrument(name = "Creating a new api key", skip(app_state, headers, body))]
+pub async fn create_api_key(
+    State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Result<Json<NewApiKey>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let management_token = headers
+        .get(MANAGEMENT_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or(LinkError::Unauthorized)?;
+    let owner_id = get_owner_by_token(&app_state.pool, management_token)
+        .await
+        .map_err(LinkError::SqlxError)?
+        .ok_or(LinkError::Unauthorized)?;
+
+    let Json(new_key) = body?;
+    let name = new_key.name.trim().to_string();
+    if name.is_empty() {
+        return Err(ApiKeyError::MissingName.into());
+    }
+    if let Some(scope) = new_key
+        .scopes
+        .iter()
+        .find(|scope| !SCOPES.contains(&scope.as_str()))
+    {
+        return Err(ApiKeyError::InvalidScope(scope.clone()).into());
+    }
+
+    let key = format!("usk_{}", generate_token(40));
+    let id = insert_api_key(&app_state.pool, owner_id, &name, &key, &new_key.scopes)
+        .await
+        .map_err(LinkError::SqlxError)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedApiKey {
+            id,
+            name,
+            scopes: new_key.scopes,
+            key,
+        }),
+    ))
+}
+
+#[tracing::instrument(name = "Saving new api key in the database", skip(pool, key))]
+pub async fn insert_api_key(
+    pool: &PgPool,
+    owner_id: Uuid,
+    name: &str,
+    key: &str,
+    scopes: &[String],
+) -> Result<Uuid, sqlx::Error> {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+    INSERT INTO api_keys (id, owner_id, name, key_hash, scopes, created_at)
+    VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        id,
+        owner_id,
+        name,
+        hash_token(key),
+        scopes,
+        Utc::now()
+    )
+    .execute(pool)
+    .await?;
+    Ok(id)
+}
+
+#[tracing::instrument(name = "Authenticate an api key", skip_all)]
+pub async fn get_api_key(pool: &PgPool, key: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+    sqlx::query_as!(
+        ApiKey,
+        "SELECT id, owner_id, name, scopes FROM api_keys WHERE key_hash = $1",
+        hash_token(key)
+    )
+    .fetch_optional(pool)
+    .await
+}
@@ -1,15 +1,24 @@
-use axum::response::{Html, IntoResponse};
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+};
 use rinja_axum::Template;
 
+use crate::startup::AppState;
+
 #[derive(Template)]
 #[template(path = "index.html")]
 struct FormBaseTemplate {
     title: String,
+    base_path: String,
 }
 
-pub async fn index() -> impl IntoResponse {
+pub async fn index(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
     let template = FormBaseTemplate {
         title: String::from("url-shortener"),
+        base_path: app_state.base_url.base_path().to_string(),
     };
     Html(template.render().unwrap())
 }
@@ -0,0 +1,178 @@
+use std::sync::Arc;
+
+use axum::{
+    Form,
+    extract::State,
+    response::{Html, IntoResponse},
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use rinja_axum::Template;
+use sqlx::PgPool;
+
+use crate::{
+    routes::{LinkError, ManagementForm, authorize_owner},
+    startup::AppState,
+};
+
+/// How far back the per day chart goes.
+const CHART_DAYS: i32 = 30;
+
+#[derive(Template)]
+#[template(path = "link_analytics.html")]
+struct LinkAnalyticsTemplate {
+    id: String,
+    base_path: String,
+    management_token: String,
+    totals: EventTotals,
+    days: Vec<ChartDay>,
+    recipients: Vec<RecipientRow>,
+}
+
+pub struct EventTotals {
+    pub views: i64,
+    pub registrations: i64,
+    pub confirmations: i64,
+    pub redirects: i64,
+}
+
+/// A bar of the per day chart, heights are percentages of the busiest day.
+struct ChartDay {
+    day: NaiveDate,
+    views: i64,
+    redirects: i64,
+    views_height: i64,
+    redirects_height: i64,
+}
+
+#[derive(serde::Serialize)]
+pub struct RecipientRow {
+    pub name: String,
+    pub email: String,
+    pub status: String,
+    pub expiration_date: Option<DateTime<Utc>>,
+}
+
+#[tracing::instrument(
+    name = "Show the analytics of a link",
+    skip(form, app_state),
+    fields(link_id = %form.link_id)
+)]
+pub async fn link_analytics(
+    State(app_state): State<Arc<AppState>>,
+    Form(form): Form<ManagementForm>,
+) -> Result<impl IntoResponse, LinkError> {
+    authorize_owner(&app_state.pool, &form.link_id, &form.management_token).await?;
+
+    let totals = get_event_totals(&app_state.pool, &form.link_id).await?;
+    let days = get_daily_events(&app_state.pool, &form.link_id).await?;
+    let busiest_day = days
+        .iter()
+        .map(|(_, views, redirects)| *views.max(redirects))
+        .max()
+        .unwrap_or_default()
+        .max(1);
+    let days = days
+        .into_iter()
+        .map(|(day, views, redirects)| ChartDay {
+            day,
+            views,
+            redirects,
+            views_height: views * 100 / busiest_day,
+            redirects_height: redirects * 100 / busiest_day,
+        })
+        .collect();
+    let recipients = get_link_recipients(&app_state.pool, &form.link_id).await?;
+
+    let template = LinkAnalyticsTemplate {
+        id: form.link_id,
+        base_path: app_state.base_url.base_path().to_string(),
+        management_token: form.management_token,
+        totals,
+        days,
+        recipients,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+#[tracing::instrument(name = "Count the events of a link", skip(pool))]
+pub async fn get_event_totals(pool: &PgPool, link_id: &str) -> Result<EventTotals, sqlx::Error> {
+    let totals = sqlx::query_as!(
+        EventTotals,
+        r#"
+    SELECT
+        COUNT(*) FILTER (WHERE kind = 'view') AS "views!",
+        COUNT(*) FILTER (WHERE kind = 'registration') AS "registrations!",
+        COUNT(*) FILTER (WHERE kind = 'confirmation') AS "confirmations!",
+        COUNT(*) FILTER (WHERE kind = 'redirect') AS "redirects!"
+    FROM link_events WHERE link_id = $1
+        "#,
+        link_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(totals)
+}
+
+/// Views and redirects per UTC day, days without any event are left out.
+#[tracing::instrument(name = "Count the events of a link per day", skip(pool))]
+async fn get_daily_events(
+    pool: &PgPool,
+    link_id: &str,
+) -> Result<Vec<(NaiveDate, i64, i64)>, sqlx::Error> {
+    let days = sqlx::query!(
+        r#"
+    SELECT
+        (created_at AT TIME ZONE 'UTC')::date AS "day!",
+        COUNT(*) FILTER (WHERE kind = 'view') AS "views!",
+        COUNT(*) FILTER (WHERE kind = 'redirect') AS "redirects!"
+    FROM link_events
+    WHERE link_id = $1 AND created_at > now() - make_interval(days => $2)
+    GROUP BY 1 ORDER BY 1
+        "#,
+        link_id,
+        CHART_DAYS
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(days
+        .into_iter()
+        .map(|day| (day.day, day.views, day.redirects))
+        .collect())
+}
+
+/// Every recipient that registered for the link with their furthest status,
+/// same order of precedence as `check_status`, the latest token breaking ties.
+#[tracing::instrument(name = "Get the recipients of a link", skip(pool))]
+pub async fn get_link_recipients(
+    pool: &PgPool,
+    link_id: &str,
+) -> Result<Vec<RecipientRow>, sqlx::Error> {
+    let recipients = sqlx::query_as!(
+        RecipientRow,
+        r#"
+    SELECT name AS "name!", email AS "email!", status AS "status!", expiration_date
+    FROM (
+        SELECT DISTINCT ON (link_recipients.id)
+            link_recipients.name, link_recipients.email, links_tokens.expiration_date,
+            CASE WHEN links_tokens.status = 'confirmed' AND links_tokens.expiration_date <= now()
+                THEN 'expired' ELSE links_tokens.status::text END AS status
+        FROM links_tokens
+        JOIN link_recipients ON link_recipients.id = links_tokens.recepient_id
+        WHERE links_tokens.link_id = $1
+        ORDER BY link_recipients.id, CASE
+            WHEN links_tokens.status = 'confirmed'
+                AND (links_tokens.expiration_date IS NULL OR links_tokens.expiration_date > now())
+                THEN 0
+            WHEN links_tokens.status = 'awaiting_approval' THEN 1
+            WHEN links_tokens.status = 'denied' THEN 2
+            ELSE 3 END,
+            links_tokens.created_at DESC, links_tokens.link_token
+    ) AS recipients
+    ORDER BY name, email
+        "#,
+        link_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(recipients)
+}
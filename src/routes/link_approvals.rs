@@ -0,0 +1,238 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    response::{Html, IntoResponse},
+};
+use reqwest::StatusCode;
+use rinja_axum::Template;
+use sqlx::{Postgres, Transaction};
+
+use crate::{
+    domain::{ApplicationBaseUrl, LinkTokenStatus, RecipientEmail},
+    email_outbox::enqueue_email,
+    startup::AppState,
+};
+
+#[derive(serde::Deserialize)]
+pub struct ApprovalParameters {
+    approval_token: String,
+}
+
+#[derive(Template)]
+#[template(path = "approval_decision.html")]
+struct ApprovalDecisionTemplate {
+    recipient_email: String,
+    base_path: String,
+    link_id: String,
+    approved: bool,
+}
+
+#[tracing::instrument(name = "Approve a recipient", skip(parameters, app_state))]
+pub async fn approve(
+    State(app_state): State<Arc<AppState>>,
+    parameters: Query<ApprovalParameters>,
+) -> impl IntoResponse {
+    decide(&app_state, &parameters.approval_token, true).await
+}
+
+#[tracing::instrument(name = "Deny a recipient", skip(parameters, app_state))]
+pub async fn deny(
+    State(app_state): State<Arc<AppState>>,
+    parameters: Query<ApprovalParameters>,
+) -> impl IntoResponse {
+    decide(&app_state, &parameters.approval_token, false).await
+}
+
+async fn decide(
+    app_state: &AppState,
+    approval_token: &str,
+    approved: bool,
+) -> axum::response::Response {
+    let decision = match decide_and_notify(app_state, approval_token, approved).await {
+        Ok(Some(decision)) => decision,
+        // unknown token, or the owner already decided
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!("{}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let template = ApprovalDecisionTemplate {
+        recipient_email: decision.recipient_email,
+        base_path: app_state.base_url.base_path().to_string(),
+        link_id: decision.link_id,
+        approved,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+/// The decision and the email telling an approved recipient about it are
+/// saved together, nothing is kept when either fails so the owner can use
+/// the link again.
+async fn decide_and_notify(
+    app_state: &AppState,
+    approval_token: &str,
+    approved: bool,
+) -> Result<Option<ApprovalDecision>, ApprovalError> {
+    let mut transaction = app_state.pool.begin().await?;
+    let Some(decision) = record_decision(
+        &mut transaction,
+        approval_token,
+        approved,
+        app_state.grant_validity_hours,
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+    if approved {
+        send_approval_granted_email(
+            &mut transaction,
+            &decision.recipient_email,
+            &app_state.base_url,
+            &decision.link_id,
+        )
+        .await?;
+    }
+    transaction.commit().await?;
+    Ok(Some(decision))
+}
+
+pub struct ApprovalDecision {
+    pub recipient_email: String,
+    pub link_id: String,
+}
+
+/// Approval tokens are single use, deciding twice finds nothing to update.
+///
+/// The access of an approved recipient expires like the one of a recipient
+/// who didn't need approval, counting from the approval.
+#[tracing::instrument(
+    name = "Record the decision of the owner",
+    skip(transaction, approval_token)
+)]
+pub async fn record_decision(
+    transaction: &mut Transaction<'_, Postgres>,
+    approval_token: &str,
+    approved: bool,
+    grant_validity_hours: Option<i32>,
+) -> Result<Option<ApprovalDecision>, sqlx::Error> {
+    let status = if approved {
+        LinkTokenStatus::Confirmed
+    } else {
+        LinkTokenStatus::Denied
+    };
+    let decision = sqlx::query_as!(
+        ApprovalDecision,
+        r#"
+    UPDATE links_tokens SET status = $1, approval_token = NULL,
+        expiration_date = now() + make_interval(hours => COALESCE(links.grant_validity_hours, $3))
+    FROM link_recipients, links
+    WHERE links_tokens.approval_token = $2
+        AND links_tokens.status = 'awaiting_approval'
+        AND link_recipients.id = links_tokens.recepient_id
+        AND links.id = links_tokens.link_id
+    RETURNING link_recipients.email AS recipient_email, links_tokens.link_id
+            "#,
+        status as LinkTokenStatus,
+        approval_token,
+        grant_validity_hours
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(decision)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ApprovalError {
+    #[error("couldn't fetch the approval request, sqlx error {0}")]
+    SqlxError(#[from] sqlx::Error),
+    #[error("invalid email, {0}")]
+    InvalidEmail(String),
+}
+
+/// The email is queued in the outbox along with the confirmation that
+/// issued the approval token, the owner is asked exactly when the recipient
+/// starts waiting for them.
+#[tracing::instrument(
+    name = "Ask the owner of a link to approve a recipient",
+    skip(transaction, base_url, approval_token)
+)]
+pub async fn send_approval_request_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    base_url: &ApplicationBaseUrl,
+    approval_token: &str,
+) -> Result<(), ApprovalError> {
+    let request = sqlx::query!(
+        r#"
+    SELECT link_recipients.name, link_recipients.email, links_tokens.link_id,
+        link_owners.email AS owner_email
+    FROM links_tokens
+    JOIN link_recipients ON link_recipients.id = links_tokens.recepient_id
+    JOIN links ON links.id = links_tokens.link_id
+    JOIN link_owners ON link_owners.id = links.owner_id
+    WHERE links_tokens.approval_token = $1
+            "#,
+        approval_token
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    let owner_email = RecipientEmail::parse(request.owner_email.unwrap_or_default())
+        .map_err(ApprovalError::InvalidEmail)?;
+
+    let short_link = base_url.short_link(&request.link_id);
+    let approve_link = base_url.approval_link(approval_token);
+    let deny_link = base_url.denial_link(approval_token);
+    let plain_body = format!(
+        "{} ({}) confirmed their email and asks to access your link {}.\nVisit {} to approve, or {} to deny the request.",
+        request.name, request.email, short_link, approve_link, deny_link
+    );
+    let html_body = format!(
+        "{} ({}) confirmed their email and asks to access your link {}.<br />Click <a href=\"{}\">here</a> to approve, or <a href=\"{}\">here</a> to deny the request.",
+        request.name, request.email, short_link, approve_link, deny_link
+    );
+    enqueue_email(
+        transaction,
+        &owner_email,
+        "Someone asks to access your link",
+        &html_body,
+        &plain_body,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Queued in the outbox along with the approval.
+#[tracing::instrument(
+    name = "Tell a recipient the owner approved their request",
+    skip(transaction, base_url)
+)]
+pub async fn send_approval_granted_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    recipient_email: &str,
+    base_url: &ApplicationBaseUrl,
+    link_id: &str,
+) -> Result<(), ApprovalError> {
+    let recipient_email =
+        RecipientEmail::parse(recipient_email.to_string()).map_err(ApprovalError::InvalidEmail)?;
+    let short_link = base_url.short_link(link_id);
+    let plain_body = format!(
+        "The owner of the link approved your request!\nVisit {} to access it.",
+        short_link
+    );
+    let html_body = format!(
+        "The owner of the link approved your request!<br />Click <a href=\"{}\">here</a> to access it.",
+        short_link
+    );
+    enqueue_email(
+        transaction,
+        &recipient_email,
+        "Your access request was approved",
+        &html_body,
+        &plain_body,
+    )
+    .await?;
+    Ok(())
+}
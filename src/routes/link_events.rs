@@ -0,0 +1,92 @@
+use axum::http::{HeaderMap, header};
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// What happened to a link, stored in the `kind` column of `link_events`.
+#[derive(Debug, Clone, Copy)]
+pub enum LinkEventKind {
+    /// `link_access_page` was shown.
+    View,
+    /// A recipient registered and got a confirmation email.
+    Registration,
+    /// A recipient confirmed their email.
+    Confirmation,
+    /// A recipient was redirected to the target url.
+    Redirect,
+}
+
+impl LinkEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkEventKind::View => "view",
+            LinkEventKind::Registration => "registration",
+            LinkEventKind::Confirmation => "confirmation",
+            LinkEventKind::Redirect => "redirect",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LinkEvent {
+    pub link_id: String,
+    pub kind: LinkEventKind,
+    pub recipient_id: Option<Uuid>,
+    pub user_agent: Option<String>,
+    pub referrer: Option<String>,
+}
+
+impl LinkEvent {
+    /// The user agent and referrer are taken from the request headers.
+    pub fn new(link_id: &str, kind: LinkEventKind, headers: &HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(ToOwned::to_owned)
+        };
+        Self {
+            link_id: link_id.to_owned(),
+            kind,
+            recipient_id: None,
+            user_agent: header(header::USER_AGENT),
+            referrer: header(header::REFERER),
+        }
+    }
+
+    pub fn recipient(mut self, recipient_id: Uuid) -> Self {
+        self.recipient_id = Some(recipient_id);
+        self
+    }
+}
+
+/// Store the event in the background, the request doesn't wait for it and a
+/// failure is only logged.
+pub fn record_event(pool: &PgPool, event: LinkEvent) {
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = insert_event(&pool, &event).await {
+            tracing::error!("Failed to record {:?}: {}", event, e);
+        }
+    });
+}
+
+#[tracing::instrument(name = "Saving a link event in the database", skip(pool))]
+async fn insert_event(pool: &PgPool, event: &LinkEvent) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+    INSERT INTO link_events (id, link_id, kind, recipient_id, user_agent, referrer, created_at)
+    VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        Uuid::new_v4(),
+        event.link_id,
+        event.kind.as_str(),
+        event.recipient_id,
+        event.user_agent,
+        event.referrer,
+        Utc::now()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,329 @@
+use std::sync::Arc;
+
+use axum::{
+    Form,
+    extract::State,
+    response::{Html, IntoResponse},
+};
+use chrono::{DateTime, Utc};
+use rinja_axum::Template;
+use serde::Deserialize;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    domain::{AllowlistEntry, NormalizedUrl, RecipientEmail, TargetUrl},
+    routes::{LinkError, hash_token},
+    startup::AppState,
+};
+
+/// Every management endpoint expects the short link id and the management
+/// token handed out when the link was created.
+#[derive(Deserialize)]
+pub struct ManagementForm {
+    pub link_id: String,
+    pub management_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateTargetForm {
+    link_id: String,
+    management_token: String,
+    target_url: String,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateAllowlistForm {
+    link_id: String,
+    management_token: String,
+    allowlist: String,
+}
+
+#[derive(Template)]
+#[template(path = "manage_link.html")]
+struct ManageLinkTemplate {
+    id: String,
+    base_path: String,
+    target_url: String,
+    created_at: DateTime<Utc>,
+    disabled: bool,
+    requires_approval: bool,
+    active_from: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+    click_count: i32,
+    max_clicks: Option<i32>,
+    max_recipients: Option<i32>,
+    allowlist: String,
+    management_token: String,
+}
+
+#[tracing::instrument(name = "Inspect a link", skip(form, app_state), fields(link_id = %form.link_id))]
+pub async fn manage_link(
+    State(app_state): State<Arc<AppState>>,
+    Form(form): Form<ManagementForm>,
+) -> Result<impl IntoResponse, LinkError> {
+    authorize_owner(&app_state.pool, &form.link_id, &form.management_token).await?;
+    render_manage_link(&app_state, form.link_id, form.management_token).await
+}
+
+#[tracing::instrument(
+    name = "Update the target url of a link",
+    skip(form, app_state),
+    fields(link_id = %form.link_id)
+)]
+pub async fn update_link_target(
+    State(app_state): State<Arc<AppState>>,
+    Form(form): Form<UpdateTargetForm>,
+) -> Result<impl IntoResponse, LinkError> {
+    authorize_owner(&app_state.pool, &form.link_id, &form.management_token).await?;
+    let target_url = TargetUrl::parse(&form.target_url, &app_state.target_url_policy)
+        .map_err(LinkError::InvalidUrl)?;
+    let normalized_target_url =
+        NormalizedUrl::parse(target_url.as_ref()).map_err(LinkError::InvalidUrl)?;
+
+    sqlx::query!(
+        "UPDATE links SET target_url = $1, normalized_target_url = $2 WHERE id = $3",
+        target_url.as_ref(),
+        normalized_target_url.as_ref(),
+        form.link_id
+    )
+    .execute(&app_state.pool)
+    .await?;
+
+    render_manage_link(&app_state, form.link_id, form.management_token).await
+}
+
+#[tracing::instrument(name = "Disable a link", skip(form, app_state), fields(link_id = %form.link_id))]
+pub async fn disable_link(
+    State(app_state): State<Arc<AppState>>,
+    Form(form): Form<ManagementForm>,
+) -> Result<impl IntoResponse, LinkError> {
+    authorize_owner(&app_state.pool, &form.link_id, &form.management_token).await?;
+    set_link_disabled(&app_state.pool, &form.link_id, true).await?;
+    render_manage_link(&app_state, form.link_id, form.management_token).await
+}
+
+#[tracing::instrument(name = "Enable a link", skip(form, app_state), fields(link_id = %form.link_id))]
+pub async fn enable_link(
+    State(app_state): State<Arc<AppState>>,
+    Form(form): Form<ManagementForm>,
+) -> Result<impl IntoResponse, LinkError> {
+    authorize_owner(&app_state.pool, &form.link_id, &form.management_token).await?;
+    set_link_disabled(&app_state.pool, &form.link_id, false).await?;
+    render_manage_link(&app_state, form.link_id, form.management_token).await
+}
+
+#[tracing::instrument(
+    name = "Update the recipient allowlist of a link",
+    skip(form, app_state),
+    fields(link_id = %form.link_id)
+)]
+pub async fn update_allowlist(
+    State(app_state): State<Arc<AppState>>,
+    Form(form): Form<UpdateAllowlistForm>,
+) -> Result<impl IntoResponse, LinkError> {
+    authorize_owner(&app_state.pool, &form.link_id, &form.management_token).await?;
+    let allowlist =
+        AllowlistEntry::parse_list(&form.allowlist).map_err(LinkError::InvalidAllowlist)?;
+
+    let mut transaction = app_state.pool.begin().await?;
+    replace_allowlist(&mut transaction, &form.link_id, &allowlist).await?;
+    transaction.commit().await?;
+
+    render_manage_link(&app_state, form.link_id, form.management_token).await
+}
+
+async fn render_manage_link(
+    app_state: &AppState,
+    link_id: String,
+    management_token: String,
+) -> Result<Html<String>, LinkError> {
+    let pool = &app_state.pool;
+    let link = sqlx::query!(
+        r#"
+    SELECT target_url, created_at, disabled, requires_approval, active_from, expires_at,
+        click_count, max_clicks, max_recipients
+    FROM links WHERE id = $1
+        "#,
+        link_id
+    )
+    .fetch_one(pool)
+    .await?;
+    let allowlist = get_allowlist(pool, &link_id)
+        .await?
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let template = ManageLinkTemplate {
+        id: link_id,
+        base_path: app_state.base_url.base_path().to_string(),
+        target_url: link.target_url,
+        created_at: link.created_at,
+        disabled: link.disabled,
+        requires_approval: link.requires_approval,
+        active_from: link.active_from,
+        expires_at: link.expires_at,
+        click_count: link.click_count,
+        max_clicks: link.max_clicks,
+        max_recipients: link.max_recipients,
+        allowlist,
+        management_token,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+#[tracing::instrument(name = "Set the disabled flag of a link", skip(pool))]
+pub async fn set_link_disabled(
+    pool: &PgPool,
+    link_id: &str,
+    disabled: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE links SET disabled = $1 WHERE id = $2",
+        disabled,
+        link_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Swap the whole allowlist of a link for the given entries, an empty
+/// allowlist lets anyone register.
+#[tracing::instrument(name = "Replace the allowlist of a link", skip(transaction, allowlist))]
+pub async fn replace_allowlist(
+    transaction: &mut Transaction<'_, Postgres>,
+    link_id: &str,
+    allowlist: &[AllowlistEntry],
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!("DELETE FROM link_allowlist WHERE link_id = $1", link_id);
+    transaction.execute(query).await?;
+
+    let patterns = allowlist
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    let query = sqlx::query!(
+        r#"
+    INSERT INTO link_allowlist (link_id, pattern)
+    SELECT $1, pattern FROM UNNEST($2::text[]) AS pattern
+    ON CONFLICT DO NOTHING
+        "#,
+        link_id,
+        &patterns
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get the allowlist of a link", skip(pool))]
+pub async fn get_allowlist(
+    pool: &PgPool,
+    link_id: &str,
+) -> Result<Vec<AllowlistEntry>, sqlx::Error> {
+    let allowlist = sqlx::query!(
+        "SELECT pattern FROM link_allowlist WHERE link_id = $1 ORDER BY pattern",
+        link_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    // the patterns were validated before being stored
+    .filter_map(|row| AllowlistEntry::parse(row.pattern).ok())
+    .collect();
+    Ok(allowlist)
+}
+
+/// Make sure the management token belongs to the owner of the link, returns
+/// the owner id.
+#[tracing::instrument(name = "Authorize the owner of a link", skip(pool, management_token))]
+pub async fn authorize_owner(
+    pool: &PgPool,
+    link_id: &str,
+    management_token: &str,
+) -> Result<Uuid, LinkError> {
+    let owner = sqlx::query!(
+        r#"
+    SELECT link_owners.id FROM links
+    JOIN link_owners ON links.owner_id = link_owners.id
+    WHERE links.id = $1 AND link_owners.management_token_hash = $2
+            "#,
+        link_id,
+        hash_token(management_token)
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(LinkError::Unauthorized)?;
+    Ok(owner.id)
+}
+
+#[tracing::instrument(name = "Get owner by management token", skip(pool, management_token))]
+pub async fn get_owner_by_token(
+    pool: &PgPool,
+    management_token: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let owner = sqlx::query!(
+        "SELECT id FROM link_owners WHERE management_token_hash = $1",
+        hash_token(management_token)
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(owner.map(|owner| owner.id))
+}
+
+#[tracing::instrument(name = "Get the owner of a link", skip(pool))]
+pub async fn get_link_owner(pool: &PgPool, link_id: &str) -> Result<Option<Uuid>, sqlx::Error> {
+    let link = sqlx::query!("SELECT owner_id FROM links WHERE id = $1", link_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(link.and_then(|link| link.owner_id))
+}
+
+#[tracing::instrument(name = "Saving new link owner in the database", skip_all)]
+pub async fn create_owner(
+    transaction: &mut Transaction<'_, Postgres>,
+    management_token: &str,
+    email: Option<&RecipientEmail>,
+) -> Result<Uuid, sqlx::Error> {
+    let owner_id = Uuid::new_v4();
+    let query = sqlx::query!(
+        r#"
+    INSERT INTO link_owners (id, management_token_hash, created_at, email)
+    VALUES ($1, $2, $3, $4)
+        "#,
+        owner_id,
+        hash_token(management_token),
+        Utc::now(),
+        email.map(AsRef::as_ref)
+    );
+    transaction.execute(query).await?;
+    Ok(owner_id)
+}
+
+#[tracing::instrument(name = "Update the email of a link owner", skip(transaction, email))]
+pub async fn set_owner_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    owner_id: Uuid,
+    email: &RecipientEmail,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        "UPDATE link_owners SET email = $1 WHERE id = $2",
+        email.as_ref(),
+        owner_id
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get the email of a link owner", skip(transaction))]
+pub async fn get_owner_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    owner_id: Uuid,
+) -> Result<Option<String>, sqlx::Error> {
+    let owner = sqlx::query!("SELECT email FROM link_owners WHERE id = $1", owner_id)
+        .fetch_one(&mut **transaction)
+        .await?;
+    Ok(owner.email)
+}
@@ -1,22 +1,28 @@
 use std::sync::Arc;
 
 use axum::{
-    Form,
+    Form, Json,
     extract::{Path, State},
-    http::Response,
-    response::{Html, IntoResponse},
+    http::{HeaderMap, Response, header},
+    response::{Html, IntoResponse, Redirect},
 };
-use chrono::Utc;
-use rand::{Rng, distr::Alphanumeric, rng};
-use reqwest::StatusCode;
+use chrono::{DateTime, Utc};
+use reqwest::{StatusCode, Url};
 use rinja_axum::Template;
-use serde::Deserialize;
-use sqlx::{Executor, PgPool, Postgres, Transaction};
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, Executor, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
-    domain::{NewRecipient, RecipientEmail, RecipientName},
-    email_client::EmailClient,
+    configuration::SignInMode,
+    domain::{
+        ApplicationBaseUrl, LinkTokenStatus, Locale, NewRecipient, RecipientEmail, RecipientName,
+    },
+    email_outbox::enqueue_email,
+    routes::{
+        AccessRequestTemplate, LinkError, LinkEvent, LinkEventKind, StoredLink, generate_token,
+        get_allowlist, get_available_link, record_click, record_event,
+    },
     startup::AppState,
 };
 
@@ -44,7 +50,7 @@ struct EmailNotVerified<'a> {
 
 #[tracing::instrument(
     name = "Accessing link with credentials",
-    skip(form, app_state),
+    skip(form, app_state, headers),
     fields(
         recipient_name = %form.name,
         recipient_email = %form.email
@@ -53,9 +59,17 @@ struct EmailNotVerified<'a> {
 pub async fn access_link(
     State(app_state): State<Arc<AppState>>,
     Path(link_id): Path<String>,
+    headers: HeaderMap,
     Form(form): Form<FormData>,
 ) -> Result<impl IntoResponse, RecipientError> {
-    let new_recipient = form.try_into().map_err(RecipientError::InvalidRecipient)?;
+    // anyone knowing the name and email of a recipient could pass as them
+    if app_state.sign_in_mode == SignInMode::MagicLink {
+        return Err(RecipientError::MagicLinkRequired);
+    }
+    let new_recipient: NewRecipient = form.try_into().map_err(RecipientError::InvalidRecipient)?;
+
+    let link = get_available_link(&app_state.pool, &link_id).await?;
+    ensure_recipient_is_allowed(&app_state.pool, &link_id, &new_recipient.email).await?;
 
     let recipient_id = match get_recipient(&app_state.pool, &new_recipient).await {
         Ok(recipient_id) => recipient_id,
@@ -71,28 +85,233 @@ pub async fn access_link(
         }
     };
 
-    if check_status(&app_state.pool, recipient_id, &link_id).await? == "confirmed" {
-        let target_url = get_target_link(&app_state.pool, &link_id).await?;
+    match check_status(&app_state.pool, recipient_id, &link_id).await? {
+        Some(LinkTokenStatus::Confirmed) => {
+            let redirect = send_to_target(&app_state, &link, recipient_id, &headers).await?;
+            let cookies = app_state
+                .recipient_sessions
+                .remember(&headers, recipient_id);
+            Ok((cookies, redirect).into_response())
+        }
+        Some(LinkTokenStatus::AwaitingApproval) => Ok((
+            StatusCode::FORBIDDEN,
+            Html(
+                AwaitingApproval {
+                    email: new_recipient.email.as_ref(),
+                }
+                .render()
+                .unwrap(),
+            ),
+        )
+            .into_response()),
+        _ => Ok((StatusCode::UNAUTHORIZED).into_response()),
+    }
+}
+
+/// Send a recipient remembered by their cookie straight to the target url,
+/// `None` when they don't have access to the link and have to go through the
+/// form.
+#[tracing::instrument(name = "Redirect a remembered recipient", skip_all)]
+pub async fn redirect_remembered_recipient(
+    app_state: &AppState,
+    link: &StoredLink,
+    headers: &HeaderMap,
+) -> Result<Option<axum::response::Response>, LinkError> {
+    let Some(recipient_id) = remembered_recipient(app_state, link, headers).await? else {
+        return Ok(None);
+    };
+    send_to_target(app_state, link, recipient_id, headers)
+        .await
+        .map(Some)
+}
+
+/// The recipient remembered by their cookie, if they still have access to
+/// the link.
+async fn remembered_recipient(
+    app_state: &AppState,
+    link: &StoredLink,
+    headers: &HeaderMap,
+) -> Result<Option<Uuid>, LinkError> {
+    let Some(recipient_id) = app_state.recipient_sessions.recipient(headers) else {
+        return Ok(None);
+    };
+    if check_status(&app_state.pool, recipient_id, &link.id).await?
+        != Some(LinkTokenStatus::Confirmed)
+    {
+        return Ok(None);
+    }
+    // the allowlist may have changed since the recipient confirmed
+    let allowlist = get_allowlist(&app_state.pool, &link.id).await?;
+    if !allowlist.is_empty() {
+        let allowed = get_recipient_email(&app_state.pool, recipient_id)
+            .await?
+            .is_some_and(|email| allowlist.iter().any(|entry| entry.allows(&email)));
+        if !allowed {
+            return Ok(None);
+        }
+    }
+    Ok(Some(recipient_id))
+}
+
+/// Send a recipient with access to the link to the target url, through the
+/// preview page when the owner asked for one. Json clients always get the
+/// target url directly.
+pub async fn send_to_target(
+    app_state: &AppState,
+    link: &StoredLink,
+    recipient_id: Uuid,
+    headers: &HeaderMap,
+) -> Result<axum::response::Response, LinkError> {
+    let format = RedirectFormat::from_headers(headers);
+    if link.show_preview && format != RedirectFormat::Json {
+        // the click is counted when the recipient continues
+        return Ok(preview_page(app_state, link));
+    }
+    redirect_to_target(app_state, link, recipient_id, headers).await
+}
+
+/// Count a click of a recipient with access to the link and redirect them to
+/// the target url.
+async fn redirect_to_target(
+    app_state: &AppState,
+    link: &StoredLink,
+    recipient_id: Uuid,
+    headers: &HeaderMap,
+) -> Result<axum::response::Response, LinkError> {
+    let target_url = record_click(&app_state.pool, &link.id)
+        .await?
+        .ok_or(LinkError::LimitReached)?;
+    record_event(
+        &app_state.pool,
+        LinkEvent::new(&link.id, LinkEventKind::Redirect, headers).recipient(recipient_id),
+    );
+    Ok(redirect_response(
+        RedirectFormat::from_headers(headers),
+        target_url,
+    ))
+}
+
+/// The continue button of the preview page, remembered recipients go on to
+/// the target url, anyone else is sent back to the short link.
+#[tracing::instrument(name = "Continue to the target url", skip(app_state, headers))]
+pub async fn continue_to_target(
+    State(app_state): State<Arc<AppState>>,
+    Path(link_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, LinkError> {
+    let link = get_available_link(&app_state.pool, &link_id).await?;
+    match remembered_recipient(&app_state, &link, &headers).await? {
+        Some(recipient_id) => redirect_to_target(&app_state, &link, recipient_id, &headers).await,
+        None => {
+            let short_link = app_state.base_url.short_link(&link.id);
+            Ok(Redirect::to(short_link.as_str()).into_response())
+        }
+    }
+}
+
+/// How a client wants to be sent to the target url.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedirectFormat {
+    /// Htmx requests, followed through the `HX-Redirect` header.
+    Htmx,
+    /// Clients accepting json get the target url in the body.
+    Json,
+    /// Plain form posts, browsers without js and curl get a `Location`
+    /// redirect.
+    Browser,
+}
+
+impl RedirectFormat {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        if headers
+            .get("HX-Request")
+            .is_some_and(|value| value == "true")
+        {
+            return RedirectFormat::Htmx;
+        }
+        let accepts_json = headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|accept| accept.contains("application/json"));
+        if accepts_json {
+            RedirectFormat::Json
+        } else {
+            RedirectFormat::Browser
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TargetUrlBody {
+    pub target_url: String,
+}
 
-        Ok(Response::builder()
+/// What the owner wrote about the link, shown before the recipient decides
+/// to continue to the target url.
+fn preview_page(app_state: &AppState, link: &StoredLink) -> axum::response::Response {
+    let host = Url::parse(&link.target_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_owned))
+        .unwrap_or_default();
+    let continue_link = app_state.base_url.continue_link(&link.id);
+    let template = LinkPreview {
+        host: &host,
+        target_url: &link.target_url,
+        continue_link: continue_link.as_str(),
+        title: link.title.as_deref(),
+        description: link.description.as_deref(),
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+/// Send a verified recipient to the target url the way their client expects.
+pub fn redirect_response(format: RedirectFormat, target_url: String) -> axum::response::Response {
+    match format {
+        RedirectFormat::Htmx => Response::builder()
             .status(StatusCode::SEE_OTHER) // Temporary redirect
             .header("HX-Redirect", target_url) // HTMX redirect header
             .body(axum::body::Body::empty())
-            .unwrap())
-    } else {
-        Ok((StatusCode::UNAUTHORIZED).into_response())
+            .unwrap(),
+        RedirectFormat::Json => Json(TargetUrlBody { target_url }).into_response(),
+        RedirectFormat::Browser => Redirect::to(&target_url).into_response(),
     }
 }
 
+/// Shown instead of redirecting when the owner asked for a preview, the
+/// continue button goes through `continue_to_target`, which counts the click.
+#[derive(Template)]
+#[template(path = "link_preview.html")]
+struct LinkPreview<'a> {
+    host: &'a str,
+    target_url: &'a str,
+    continue_link: &'a str,
+    title: Option<&'a str>,
+    description: Option<&'a str>,
+}
+
+#[derive(Template)]
+#[template(path = "awaiting_approval.html")]
+struct AwaitingApproval<'a> {
+    email: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "recipient_not_allowed.html")]
+struct RecipientNotAllowed<'a> {
+    email: &'a str,
+}
+
 #[derive(Template)]
 #[template(path = "success_email.html")]
 struct SucessEmail {
+    link_id: String,
+    base_path: String,
     recipient_email: String,
 }
 
 #[tracing::instrument(
     name = "Adding a new recipient",
-    skip(form, app_state),
+    skip(form, app_state, headers),
     fields(
         recipient_name = %form.name,
         recipient_email = %form.email
@@ -101,36 +320,113 @@ struct SucessEmail {
 pub async fn add_recipient(
     State(app_state): State<Arc<AppState>>,
     Path(requested_link): Path<String>,
+    headers: HeaderMap,
     Form(form): Form<FormData>,
 ) -> Result<impl IntoResponse, RecipientError> {
-    let new_recipient = form.try_into().map_err(RecipientError::InvalidRecipient)?;
+    let new_recipient: NewRecipient = form.try_into().map_err(RecipientError::InvalidRecipient)?;
+    let recipient_email = new_recipient.email.as_ref().to_owned();
+
+    match register_recipient(&app_state, requested_link.clone(), new_recipient, &headers).await? {
+        Registration::EmailSent => Ok(Html(
+            SucessEmail {
+                link_id: requested_link,
+                base_path: app_state.base_url.base_path().to_string(),
+                recipient_email,
+            }
+            .render()
+            .unwrap(),
+        )
+        .into_response()),
+        Registration::AlreadyConfirmed => {
+            Ok(String::from("user already confirmed the link, you should verify").into_response())
+        }
+        Registration::AwaitingApproval => Ok(Html(
+            AwaitingApproval {
+                email: &recipient_email,
+            }
+            .render()
+            .unwrap(),
+        )
+        .into_response()),
+        Registration::Denied => Ok((
+            StatusCode::FORBIDDEN,
+            Html(
+                AccessRequestTemplate {
+                    denied: true,
+                    base_path: app_state.base_url.base_path().to_string(),
+                }
+                .render()
+                .unwrap(),
+            ),
+        )
+            .into_response()),
+    }
+}
+
+/// The outcome of registering a recipient for a link.
+#[derive(Debug, PartialEq)]
+pub enum Registration {
+    /// A confirmation email was sent.
+    EmailSent,
+    /// Nothing to do, the recipient can already get the link.
+    AlreadyConfirmed,
+    /// Nothing to do, the owner still has to approve the recipient.
+    AwaitingApproval,
+    /// Nothing to do, the owner denied the recipient.
+    Denied,
+}
+
+/// Register a recipient and send them a confirmation email, shared by the
+/// html form and the json api.
+pub async fn register_recipient(
+    app_state: &AppState,
+    requested_link: String,
+    new_recipient: NewRecipient,
+    headers: &HeaderMap,
+) -> Result<Registration, RecipientError> {
+    let link = get_available_link(&app_state.pool, &requested_link).await?;
+    ensure_recipient_is_allowed(&app_state.pool, &requested_link, &new_recipient.email).await?;
+    let locale = headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map(Locale::from_accept_language)
+        .unwrap_or_default();
 
     let mut transaction = app_state.pool.begin().await?;
 
     // if it's an already duplicated email, check if it's status confirmed with
-    // the requested link
-    let recipient_id = match insert_recipient(&mut transaction, &new_recipient).await {
-        Ok(recipient_id) => recipient_id,
+    // the requested link, a unique violation aborts the whole transaction so
+    // only roll back to before the insert
+    let mut savepoint = Connection::begin(&mut *transaction).await?;
+    let recipient_id = match insert_recipient(&mut savepoint, &new_recipient, locale).await {
+        Ok(recipient_id) => {
+            savepoint.commit().await?;
+            recipient_id
+        }
         Err(e) => {
+            savepoint.rollback().await?;
             match &e {
-                sqlx::Error::Database(db_err) => {
-                    if db_err.is_unique_violation() {
-                        let recipient_id = get_recipient(&app_state.pool, &new_recipient).await?;
-                        if check_status(&app_state.pool, recipient_id, &requested_link).await?
-                            == "confirmed"
-                        {
-                            return Ok(String::from(
-                                "user already confirmed the link, you should verify",
-                            )
-                            .into_response());
-                        } else {
-                            // if the recipient has a registered email but  has not
-                            // confirmed the link or recieved a link yet, we can proceed
-                            // to send him a new confirmation email
+                sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                    let recipient_id = get_recipient(&app_state.pool, &new_recipient).await?;
+                    match check_status(&app_state.pool, recipient_id, &requested_link).await? {
+                        Some(LinkTokenStatus::Confirmed) => {
+                            return Ok(Registration::AlreadyConfirmed);
+                        }
+                        Some(LinkTokenStatus::AwaitingApproval) => {
+                            return Ok(Registration::AwaitingApproval);
+                        }
+                        // the owner already decided, registering again would
+                        // only ask them once more
+                        Some(LinkTokenStatus::Denied) => {
+                            return Ok(Registration::Denied);
+                        }
+                        // if the recipient has a registered email but  has not
+                        // confirmed the link or recieved a link yet, we can proceed
+                        // to send him a new confirmation email
+                        _ => {
+                            update_recipient_locale(&mut transaction, recipient_id, locale).await?;
                             recipient_id
                         }
-                    } else {
-                        return Err(RecipientError::SqlxError(e));
                     }
                 }
                 _ => return Err(RecipientError::SqlxError(e)),
@@ -138,65 +434,299 @@ pub async fn add_recipient(
         }
     };
 
-    let link_token = generate_link_token();
+    // confirmation enforces the limit again, this only saves the recipient an
+    // email that can't be used
+    if let Some(max_recipients) = link.max_recipients
+        && count_other_recipients(&mut *transaction, &requested_link, recipient_id).await?
+            >= i64::from(max_recipients)
+    {
+        return Err(LinkError::LimitReached.into());
+    }
 
-    store_token(&mut transaction, recipient_id, &link_token, requested_link).await?;
+    let link_token = rotate_link_token(app_state, &mut transaction, &link, recipient_id).await?;
+    send_confirmation_email(
+        &mut transaction,
+        new_recipient,
+        locale,
+        &link,
+        &app_state.base_url,
+        &link_token,
+    )
+    .await?;
 
     transaction.commit().await?;
+    record_event(
+        &app_state.pool,
+        LinkEvent::new(&link.id, LinkEventKind::Registration, headers).recipient(recipient_id),
+    );
 
-    let recipient_email = new_recipient.email.as_ref().to_owned();
+    Ok(Registration::EmailSent)
+}
+
+#[derive(Deserialize)]
+pub struct ResendForm {
+    email: String,
+}
+
+/// Send a new confirmation email to a recipient who registered for the link
+/// but didn't confirm, the link of the previous email stops working.
+#[tracing::instrument(
+    name = "Resend a confirmation email",
+    skip(form, app_state),
+    fields(recipient_email = %form.email)
+)]
+pub async fn resend_confirmation(
+    State(app_state): State<Arc<AppState>>,
+    Path(link_id): Path<String>,
+    Form(form): Form<ResendForm>,
+) -> Result<impl IntoResponse, RecipientError> {
+    let email = RecipientEmail::parse(form.email).map_err(RecipientError::InvalidRecipient)?;
+    let link = get_available_link(&app_state.pool, &link_id).await?;
+    ensure_recipient_is_allowed(&app_state.pool, &link.id, &email).await?;
 
+    let Some(recipient) = get_pending_recipient(&app_state.pool, &email, &link.id).await? else {
+        return Err(RecipientError::NothingToResend);
+    };
+    let new_recipient = NewRecipient {
+        name: RecipientName::parse(recipient.name).map_err(RecipientError::InvalidRecipient)?,
+        email,
+    };
+
+    let recipient_email = new_recipient.email.as_ref().to_owned();
+    let mut transaction = app_state.pool.begin().await?;
+    let link_token = rotate_link_token(&app_state, &mut transaction, &link, recipient.id).await?;
     send_confirmation_email(
-        &app_state.email_client,
+        &mut transaction,
         new_recipient,
-        &app_state.base_url.0,
+        Locale::parse(&recipient.locale).unwrap_or_default(),
+        &link,
+        &app_state.base_url,
         &link_token,
     )
     .await?;
+    transaction.commit().await?;
+
+    Ok(Html(
+        SucessEmail {
+            link_id: link.id,
+            base_path: app_state.base_url.base_path().to_string(),
+            recipient_email,
+        }
+        .render()
+        .unwrap(),
+    ))
+}
+
+struct PendingRecipient {
+    id: Uuid,
+    name: String,
+    locale: String,
+}
+
+/// The recipient with this email, if their furthest status with the link is
+/// still `pending`.
+#[tracing::instrument(name = "Get a pending recipient", skip(pool, email))]
+async fn get_pending_recipient(
+    pool: &PgPool,
+    email: &RecipientEmail,
+    link_id: &str,
+) -> Result<Option<PendingRecipient>, sqlx::Error> {
+    let Some(recipient) = sqlx::query_as!(
+        PendingRecipient,
+        "SELECT id, name, locale FROM link_recipients WHERE email = $1",
+        email.as_ref()
+    )
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+    let status = check_status(pool, recipient.id, link_id).await?;
+    Ok((status == Some(LinkTokenStatus::Pending)).then_some(recipient))
+}
+
+pub struct IssuedLinkToken {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Store a new link token for the recipient, the pending link tokens they
+/// got before for the link stop working.
+///
+/// Refused until `resend_cooldown_seconds` passed since the latest one, the
+/// recipient row is locked so two resends at once can't both get through.
+#[tracing::instrument(
+    name = "Rotate the link token of a recipient",
+    skip(app_state, transaction, link)
+)]
+pub async fn rotate_link_token(
+    app_state: &AppState,
+    transaction: &mut Transaction<'_, Postgres>,
+    link: &StoredLink,
+    recipient_id: Uuid,
+) -> Result<IssuedLinkToken, RecipientError> {
+    lock_recipient(transaction, recipient_id).await?;
+
+    let latest = sqlx::query!(
+        r#"
+    SELECT max(created_at) AS latest FROM links_tokens
+    WHERE recepient_id = $1 AND link_id = $2
+            "#,
+        recipient_id,
+        link.id
+    )
+    .fetch_one(&mut **transaction)
+    .await?
+    .latest;
+    if let Some(seconds) = cooldown_remaining(latest, app_state.resend_cooldown_seconds) {
+        return Err(RecipientError::ResendCooldown(seconds));
+    }
+
+    // expired tokens show the page asking for a new one
+    sqlx::query!(
+        r#"
+    UPDATE links_tokens SET expiration_date = now()
+    WHERE recepient_id = $1 AND link_id = $2 AND status = 'pending' AND confirmed_at IS NULL
+        AND (expiration_date IS NULL OR expiration_date > now())
+            "#,
+        recipient_id,
+        link.id
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    let token = generate_token(LINK_TOKEN_LENGTH);
+    let validity_hours = link
+        .link_token_validity_hours
+        .unwrap_or(app_state.link_token_validity_hours);
+    let expires_at = store_token(
+        transaction,
+        recipient_id,
+        &token,
+        link.id.clone(),
+        chrono::Duration::hours(validity_hours.into()),
+    )
+    .await?;
+    Ok(IssuedLinkToken { token, expires_at })
+}
 
-    Ok(Html(SucessEmail { recipient_email }.render().unwrap()).into_response())
+/// Lock the recipient row until the transaction ends, so two emails to the
+/// same recipient can't both get past the cooldown.
+pub async fn lock_recipient(
+    transaction: &mut Transaction<'_, Postgres>,
+    recipient_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "SELECT id FROM link_recipients WHERE id = $1 FOR UPDATE",
+        recipient_id
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(())
 }
 
-fn generate_link_token() -> String {
-    let mut rng = rng();
-    std::iter::repeat_with(|| rng.sample(Alphanumeric))
-        .map(char::from)
-        .take(25)
-        .collect()
+/// Seconds left before another email can be sent to a recipient, if the
+/// latest one, sent at `latest`, is still within `cooldown_seconds`.
+pub fn cooldown_remaining(latest: Option<DateTime<Utc>>, cooldown_seconds: i64) -> Option<i64> {
+    let wait = latest? + chrono::Duration::seconds(cooldown_seconds) - Utc::now();
+    // rounded up, "try again in 0 seconds" would be a lie
+    (wait > chrono::Duration::zero()).then(|| (wait.num_milliseconds() + 999) / 1000)
 }
 
+/// Refuse emails that don't match the allowlist of the link, if it has one.
+#[tracing::instrument(name = "Check the allowlist of a link", skip(pool, email))]
+pub async fn ensure_recipient_is_allowed(
+    pool: &PgPool,
+    link_id: &str,
+    email: &RecipientEmail,
+) -> Result<(), RecipientError> {
+    let allowlist = get_allowlist(pool, link_id).await?;
+    if allowlist.is_empty() || allowlist.iter().any(|entry| entry.allows(email)) {
+        Ok(())
+    } else {
+        Err(RecipientError::NotAllowed(email.as_ref().to_string()))
+    }
+}
+
+pub const LINK_TOKEN_LENGTH: usize = 25;
+
+/// What the confirmation email templates in `templates/email/` are given,
+/// each one picks the variant of `locale`.
+pub struct ConfirmationEmail<'a> {
+    pub locale: Locale,
+    pub name: &'a str,
+    /// The description of the link, left by its owner.
+    pub note: Option<&'a str>,
+    /// Host of the short link, the recipient registered on it.
+    pub host: &'a str,
+    pub expires_at: String,
+    pub confirmation_link: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/confirmation_subject.txt")]
+struct ConfirmationSubject<'a> {
+    email: &'a ConfirmationEmail<'a>,
+}
+
+#[derive(Template)]
+#[template(path = "email/confirmation.html")]
+struct ConfirmationHtml<'a> {
+    email: &'a ConfirmationEmail<'a>,
+}
+
+#[derive(Template)]
+#[template(path = "email/confirmation.txt")]
+struct ConfirmationText<'a> {
+    email: &'a ConfirmationEmail<'a>,
+}
+
+impl ConfirmationEmail<'_> {
+    /// The subject, html and plain text bodies.
+    pub fn render(&self) -> (String, String, String) {
+        (
+            ConfirmationSubject { email: self }.render().unwrap(),
+            ConfirmationHtml { email: self }.render().unwrap(),
+            ConfirmationText { email: self }.render().unwrap(),
+        )
+    }
+}
+
+/// The email is queued in the outbox, it's only sent once the transaction
+/// storing the link token commits.
 #[tracing::instrument(
     name = "Send a confirmation email to a new recipient",
-    skip(email_client, new_recipient, base_url, link_token)
+    skip(transaction, new_recipient, link, base_url, link_token)
 )]
 pub async fn send_confirmation_email(
-    email_client: &EmailClient,
+    transaction: &mut Transaction<'_, Postgres>,
     new_recipient: NewRecipient,
-    base_url: &str,
-    link_token: &str,
-) -> Result<(), reqwest::Error> {
-    // TODO: hard coded port, just for testing locally
-    // remove it in production as it's not needed with a real domain
-    let confirmation_link = format!(
-        "{}:8080/link_recipients/confirm?link_token={}",
-        base_url, link_token
-    );
-    let plain_body = format!(
-        "Welcome to the close friends url-shortener!\nVisit {} to verify your credentials to visit the link!",
-        confirmation_link
-    );
-    let html_body = format!(
-        "Welcome to to the close friends url-shortener!<br />Click <a href=\"{}\">here</a> to verify your credentials to visit the link!",
-        confirmation_link
-    );
-    email_client
-        .send_email(
-            new_recipient.email,
-            "A friend wants to show you something!",
-            &html_body,
-            &plain_body,
-        )
-        .await
+    locale: Locale,
+    link: &StoredLink,
+    base_url: &ApplicationBaseUrl,
+    link_token: &IssuedLinkToken,
+) -> Result<(), sqlx::Error> {
+    let confirmation_link = base_url.confirmation_link(&link_token.token);
+    let (subject, html_body, plain_body) = ConfirmationEmail {
+        locale,
+        name: new_recipient.name.as_ref(),
+        note: link.description.as_deref(),
+        host: base_url.host(),
+        expires_at: link_token
+            .expires_at
+            .format("%Y-%m-%d %H:%M UTC")
+            .to_string(),
+        confirmation_link: confirmation_link.as_str(),
+    }
+    .render();
+    enqueue_email(
+        transaction,
+        &new_recipient.email,
+        &subject,
+        &html_body,
+        &plain_body,
+    )
+    .await
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -205,10 +735,18 @@ pub enum RecipientError {
     InvalidRecipient(String),
     #[error("couldn't insert new_recipient to the database, sqlx error {0}")]
     SqlxError(#[from] sqlx::Error),
-    #[error("couldn't send email, reqwest error {0}")]
-    ReqwestError(#[from] reqwest::Error),
     #[error("duplicate email")]
     DuplicateEmail,
+    #[error("{0} is not in the allowlist of the link")]
+    NotAllowed(String),
+    #[error("returning recipients have to sign in with a link sent to their email")]
+    MagicLinkRequired,
+    #[error("no confirmation email is waiting to be confirmed")]
+    NothingToResend,
+    #[error("a confirmation email was just sent, try again in {0} seconds")]
+    ResendCooldown(i64),
+    #[error(transparent)]
+    LinkError(#[from] LinkError),
 }
 
 impl IntoResponse for RecipientError {
@@ -222,15 +760,40 @@ impl IntoResponse for RecipientError {
                 tracing::error!("{}", e);
                 StatusCode::BAD_REQUEST.into_response()
             }
-            RecipientError::ReqwestError(e) => {
-                tracing::error!("{}", e);
-                StatusCode::INTERNAL_SERVER_ERROR.into_response()
-            }
             RecipientError::DuplicateEmail => {
                 tracing::error!("Duplicate email error occurred");
                 let html = "<h1>Email already registered</h1><p>Please use a different email, or try to sign in</p>".to_string();
                 (StatusCode::CONFLICT, Html(html)).into_response()
             }
+            RecipientError::NotAllowed(email) => {
+                tracing::error!("{} is not in the allowlist of the link", email);
+                let template = RecipientNotAllowed { email: &email };
+                (StatusCode::FORBIDDEN, Html(template.render().unwrap())).into_response()
+            }
+            RecipientError::MagicLinkRequired => {
+                tracing::error!("{}", RecipientError::MagicLinkRequired);
+                let html = "<h1>Sign in by email</h1><p>Ask for a sign-in link with the email you registered with</p>".to_string();
+                (StatusCode::FORBIDDEN, Html(html)).into_response()
+            }
+            RecipientError::NothingToResend => {
+                tracing::error!("{}", RecipientError::NothingToResend);
+                let html = "<h1>Nothing to resend</h1><p>Register with the form to get a confirmation email</p>".to_string();
+                (StatusCode::NOT_FOUND, Html(html)).into_response()
+            }
+            RecipientError::ResendCooldown(seconds) => {
+                tracing::warn!("{}", RecipientError::ResendCooldown(seconds));
+                let html = format!(
+                    "<h1>Email already sent</h1><p>Check your inbox, or try again in {} seconds</p>",
+                    seconds
+                );
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, seconds.to_string())],
+                    Html(html),
+                )
+                    .into_response()
+            }
+            RecipientError::LinkError(e) => e.into_response(),
         }
     }
 }
@@ -243,34 +806,51 @@ pub async fn check_status(
     pool: &PgPool,
     recipient_id: Uuid,
     requested_link: &str,
-) -> Result<String, sqlx::Error> {
-    let query = sqlx::query!(
+) -> Result<Option<LinkTokenStatus>, sqlx::Error> {
+    let status = sqlx::query!(
         r#"
-    SELECT status FROM links_tokens WHERE recepient_id = $1 AND link_id = $2
+    SELECT status AS "status: LinkTokenStatus"
+    FROM links_tokens WHERE recepient_id = $1 AND link_id = $2
+        -- `expiration_date` of a confirmed token is when its access runs out
+        AND (status <> 'confirmed' OR expiration_date IS NULL OR expiration_date > now())
+    -- a recipient may have registered more than once, the furthest status wins
+    ORDER BY CASE status
+        WHEN 'confirmed' THEN 0
+        WHEN 'awaiting_approval' THEN 1
+        WHEN 'denied' THEN 2
+        ELSE 3 END
+    LIMIT 1
             "#,
         recipient_id,
         requested_link
     )
     .fetch_optional(pool)
     .await?
-    .map(|record_row| record_row.status)
-    .unwrap_or("no status found".to_string());
+    .map(|record_row| record_row.status);
 
-    Ok(query)
+    Ok(status)
 }
 
-#[tracing::instrument(name = "Get target url link", skip(pool))]
-pub async fn get_target_link(pool: &PgPool, link_id: &str) -> Result<String, sqlx::Error> {
-    let target_url = sqlx::query!(
+/// Distinct recipients, other than `recipient_id`, that were granted access
+/// to the link or are waiting for the owner to approve them, both count
+/// towards `max_recipients`.
+#[tracing::instrument(name = "Count the other recipients of a link", skip(executor))]
+pub async fn count_other_recipients<'c>(
+    executor: impl Executor<'c, Database = Postgres>,
+    link_id: &str,
+    recipient_id: Uuid,
+) -> Result<i64, sqlx::Error> {
+    let record = sqlx::query!(
         r#"
-    SELECT target_url FROM links WHERE id = $1
-            "#,
-        link_id
+    SELECT COUNT(DISTINCT recepient_id) AS "count!" FROM links_tokens
+    WHERE link_id = $1 AND recepient_id <> $2 AND status IN ('confirmed', 'awaiting_approval')
+        "#,
+        link_id,
+        recipient_id
     )
-    .fetch_one(pool)
-    .await?
-    .target_url;
-    Ok(target_url)
+    .fetch_one(executor)
+    .await?;
+    Ok(record.count)
 }
 
 #[tracing::instrument(
@@ -293,6 +873,20 @@ pub async fn get_recipient(
     Ok(query.id)
 }
 
+#[tracing::instrument(name = "Get the email of a recipient", skip(pool))]
+pub async fn get_recipient_email(
+    pool: &PgPool,
+    recipient_id: Uuid,
+) -> Result<Option<RecipientEmail>, sqlx::Error> {
+    let recipient = sqlx::query!(
+        "SELECT email FROM link_recipients WHERE id = $1",
+        recipient_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(recipient.and_then(|recipient| RecipientEmail::parse(recipient.email).ok()))
+}
+
 #[tracing::instrument(
     name = "Saving new recipient details in the database",
     skip(new_recipient, transaction)
@@ -300,22 +894,40 @@ pub async fn get_recipient(
 pub async fn insert_recipient(
     transaction: &mut Transaction<'_, Postgres>,
     new_recipient: &NewRecipient,
+    locale: Locale,
 ) -> Result<Uuid, sqlx::Error> {
     let recipient_id = Uuid::new_v4();
     let query = sqlx::query!(
         r#"
-    INSERT INTO link_recipients (id, email, name, received_link_at)
-    VALUES ($1, $2, $3, $4)
+    INSERT INTO link_recipients (id, email, name, received_link_at, locale)
+    VALUES ($1, $2, $3, $4, $5)
             "#,
         recipient_id,
         new_recipient.email.as_ref(),
         new_recipient.name.as_ref(),
-        Utc::now()
+        Utc::now(),
+        locale.as_str()
     );
     transaction.execute(query).await?;
     Ok(recipient_id)
 }
 
+/// Emails follow the language of the latest registration.
+#[tracing::instrument(name = "Update the locale of a recipient", skip(transaction))]
+pub async fn update_recipient_locale(
+    transaction: &mut Transaction<'_, Postgres>,
+    recipient_id: Uuid,
+    locale: Locale,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        "UPDATE link_recipients SET locale = $1 WHERE id = $2",
+        locale.as_str(),
+        recipient_id
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
 #[tracing::instrument(
     name = "Store link token in the database",
     skip(link_token, transaction)
@@ -325,21 +937,71 @@ pub async fn store_token(
     recipient_id: Uuid,
     link_token: &str,
     requested_link: String,
-) -> Result<(), sqlx::Error> {
+    validity: chrono::Duration,
+) -> Result<DateTime<Utc>, sqlx::Error> {
     // get requested_link id from links table
     // insert into links_tokens table
-    let after_week = Utc::now() + chrono::Duration::weeks(1);
+    let now = Utc::now();
+    let expiration_date = now + validity;
     let query = sqlx::query!(
         r#"
-    INSERT INTO links_tokens (link_token , recepient_id , link_id , status , expiration_date )
-    VALUES ($1, $2, $3, $4, $5)
+    INSERT INTO links_tokens (link_token , recepient_id , link_id , status , expiration_date,
+        created_at)
+    VALUES ($1, $2, $3, $4, $5, $6)
         "#,
         link_token,
         recipient_id,
         requested_link,
-        "pending".to_string(),
-        after_week
+        LinkTokenStatus::Pending as LinkTokenStatus,
+        expiration_date,
+        now
     );
     transaction.execute(query).await?;
-    Ok(())
+    Ok(expiration_date)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{domain::Locale, routes::ConfirmationEmail};
+
+    fn email(locale: Locale, note: Option<&str>) -> ConfirmationEmail<'_> {
+        ConfirmationEmail {
+            locale,
+            name: "hamada",
+            note,
+            host: "short.example.com",
+            expires_at: "2026-10-24 14:00 UTC".to_string(),
+            confirmation_link: "https://short.example.com/link_recipients/confirm?link_token=abc",
+        }
+    }
+
+    #[test]
+    fn every_variable_ends_up_in_the_email() {
+        let (subject, html, text) = email(Locale::En, Some("our Q3 roadmap")).render();
+        assert_eq!(subject, "A friend wants to show you something!");
+        for body in [html, text] {
+            assert!(body.contains("Hi hamada,"));
+            assert!(body.contains("short.example.com"));
+            assert!(body.contains("our Q3 roadmap"));
+            assert!(body.contains("2026-10-24 14:00 UTC"));
+            assert!(body.contains("link_token=abc"));
+        }
+    }
+
+    #[test]
+    fn the_variant_of_the_locale_is_used() {
+        let (subject, html, text) = email(Locale::Fr, None).render();
+        assert_eq!(subject, "Un ami veut vous montrer quelque chose !");
+        assert!(html.contains("Bonjour hamada,"));
+        assert!(text.contains("Bonjour hamada,"));
+        assert!(!text.contains("note"));
+    }
+
+    #[test]
+    fn the_note_is_escaped_in_the_html_body_only() {
+        let (_, html, text) = email(Locale::En, Some("<b>bold</b>")).render();
+        assert!(html.contains("&#60;b&#62;bold&#60;/b&#62;"));
+        assert!(!html.contains("<b>bold</b>"));
+        assert!(text.contains("<b>bold</b>"));
+    }
 }
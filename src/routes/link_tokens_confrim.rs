@@ -2,45 +2,358 @@ use std::sync::Arc;
 
 use axum::{
     extract::{Query, State},
-    response::{Html, IntoResponse},
+    http::HeaderMap,
+    response::{Html, IntoResponse, Response},
 };
+use chrono::Utc;
 use reqwest::StatusCode;
 use rinja_axum::Template;
 use sqlx::PgPool;
+use uuid::Uuid;
 
-use crate::startup::AppState;
+use crate::{
+    configuration::AfterConfirmation,
+    domain::{ApplicationBaseUrl, LinkTokenStatus},
+    routes::{
+        ApprovalError, LINK_TOKEN_LENGTH, LinkError, LinkEvent, LinkEventKind,
+        count_other_recipients, generate_token, get_available_link, record_event,
+        send_approval_request_email, send_to_target,
+    },
+    startup::AppState,
+};
 
 #[derive(serde::Deserialize)]
 pub struct Parameters {
     link_token: String,
 }
 
-#[tracing::instrument(name = "Confirm a pending recepient", skip(parameters, app_state))]
+#[tracing::instrument(
+    name = "Confirm a pending recepient",
+    skip(parameters, app_state, headers)
+)]
 pub async fn confirm(
     State(app_state): State<Arc<AppState>>,
     parameters: Query<Parameters>,
-) -> impl IntoResponse {
-    let _link_id = match confirm_recipient(&app_state.pool, &parameters.link_token).await {
-        Ok(link) => link,
-        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-    };
+    headers: HeaderMap,
+) -> Response {
+    let base_path = app_state.base_url.base_path().to_string();
+    let confirmed_token =
+        match confirm_link_token(&app_state, &parameters.link_token, &headers).await {
+            Ok(confirmed_token) => confirmed_token,
+            Err(e) => return e.into_page(base_path),
+        };
+    let short_link = app_state
+        .base_url
+        .short_link(&confirmed_token.link_id)
+        .to_string();
+
+    match confirmed_token.outcome {
+        Confirmation::Confirmed(LinkTokenStatus::Confirmed) => {
+            let cookies = app_state
+                .recipient_sessions
+                .remember(&headers, confirmed_token.recipient_id);
+            let continue_to = match app_state.after_confirmation {
+                AfterConfirmation::SuccessPage => None,
+                // the remembered recipient goes straight through the short link
+                AfterConfirmation::ContinuePage => Some(short_link),
+                AfterConfirmation::Redirect => {
+                    let redirect = redirect_confirmed_recipient(
+                        &app_state,
+                        &confirmed_token.link_id,
+                        confirmed_token.recipient_id,
+                        &headers,
+                    )
+                    .await;
+                    return (cookies, redirect).into_response();
+                }
+            };
+            let template = EmailVerifiedSuccessTemplate {
+                continue_to,
+                base_path,
+            };
+            (cookies, Html(template.render().unwrap())).into_response()
+        }
+        Confirmation::Confirmed(status) => Html(
+            AccessRequestTemplate {
+                denied: status == LinkTokenStatus::Denied,
+                base_path,
+            }
+            .render()
+            .unwrap(),
+        )
+        .into_response(),
+        // the token was used before, nothing changes and the recipient isn't
+        // remembered again by whoever holds the old email
+        Confirmation::AlreadyUsed(status) => Html(
+            AlreadyConfirmedTemplate {
+                short_link,
+                status: status.as_str(),
+                base_path,
+            }
+            .render()
+            .unwrap(),
+        )
+        .into_response(),
+        Confirmation::Expired => (
+            StatusCode::GONE,
+            Html(
+                LinkTokenExpiredTemplate {
+                    short_link,
+                    base_path,
+                }
+                .render()
+                .unwrap(),
+            ),
+        )
+            .into_response(),
+        Confirmation::LimitReached => LinkError::LimitReached.into_response(),
+    }
+}
+
+/// Send a recipient who just confirmed their email straight to the target
+/// url, the link may have become unavailable in the meantime.
+async fn redirect_confirmed_recipient(
+    app_state: &AppState,
+    link_id: &str,
+    recipient_id: Uuid,
+    headers: &HeaderMap,
+) -> Result<Response, LinkError> {
+    let link = get_available_link(&app_state.pool, link_id).await?;
+    send_to_target(app_state, &link, recipient_id, headers).await
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfirmationError {
+    #[error("malformed link token")]
+    MalformedToken,
+    #[error("unknown link token")]
+    UnknownToken,
+    #[error("couldn't confirm the link token, sqlx error {0}")]
+    SqlxError(#[from] sqlx::Error),
+    #[error(transparent)]
+    ApprovalError(#[from] ApprovalError),
+}
+
+/// Confirm a link token and ask the owner for approval when the link needs
+/// it, shared by the html page and the json api.
+pub async fn confirm_link_token(
+    app_state: &AppState,
+    link_token: &str,
+    headers: &HeaderMap,
+) -> Result<ConfirmedToken, ConfirmationError> {
+    // no need to look up what `generate_token` can't have made
+    if link_token.len() != LINK_TOKEN_LENGTH
+        || !link_token.chars().all(|c| c.is_ascii_alphanumeric())
+    {
+        return Err(ConfirmationError::MalformedToken);
+    }
+
+    let confirmed_token = confirm_recipient(
+        &app_state.pool,
+        link_token,
+        app_state.grant_validity_hours,
+        &app_state.base_url,
+    )
+    .await?;
+
+    if let Confirmation::Confirmed(_) = confirmed_token.outcome {
+        record_event(
+            &app_state.pool,
+            LinkEvent::new(
+                &confirmed_token.link_id,
+                LinkEventKind::Confirmation,
+                headers,
+            )
+            .recipient(confirmed_token.recipient_id),
+        );
+    }
 
-    Html(EmailVerifiedSuccessTemplate.render().unwrap()).into_response()
+    Ok(confirmed_token)
 }
 
 #[derive(Template)]
 #[template(path = "email_verified_success.html")]
-struct EmailVerifiedSuccessTemplate;
+struct EmailVerifiedSuccessTemplate {
+    /// Short link of the continue button, if there is one.
+    continue_to: Option<String>,
+    base_path: String,
+}
 
-#[tracing::instrument(name = "Mark link_token as confirmed", skip(link_token, pool))]
-pub async fn confirm_recipient(pool: &PgPool, link_token: &str) -> Result<String, sqlx::Error> {
-    let link = sqlx::query!(
-        r#"UPDATE links_tokens SET status = 'confirmed' WHERE link_token = $1 RETURNING link_id"#,
-        link_token
+#[derive(Template)]
+#[template(path = "link_token_expired.html")]
+struct LinkTokenExpiredTemplate {
+    short_link: String,
+    base_path: String,
+}
+
+#[derive(Template)]
+#[template(path = "already_confirmed.html")]
+struct AlreadyConfirmedTemplate {
+    short_link: String,
+    status: &'static str,
+    base_path: String,
+}
+
+#[derive(Template)]
+#[template(path = "invalid_link_token.html")]
+struct InvalidLinkTokenTemplate {
+    malformed: bool,
+    base_path: String,
+}
+
+impl ConfirmationError {
+    /// The page shown for a confirmation link that can't be used, its
+    /// stylesheet is found under `base_path`.
+    fn into_page(self, base_path: String) -> Response {
+        let status = match self {
+            ConfirmationError::MalformedToken => StatusCode::UNAUTHORIZED,
+            ConfirmationError::UnknownToken => StatusCode::NOT_FOUND,
+            ConfirmationError::SqlxError(_) | ConfirmationError::ApprovalError(_) => {
+                tracing::error!("{}", self);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+        let template = InvalidLinkTokenTemplate {
+            malformed: matches!(self, ConfirmationError::MalformedToken),
+            base_path,
+        };
+        (status, Html(template.render().unwrap())).into_response()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "access_request.html")]
+pub struct AccessRequestTemplate {
+    pub denied: bool,
+    pub base_path: String,
+}
+
+/// What confirming a link token did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confirmation {
+    /// The token was used for the first time, with the new status of the
+    /// recipient, `confirmed` or `awaiting_approval`.
+    Confirmed(LinkTokenStatus),
+    /// The token was used before, with the current status of the recipient.
+    AlreadyUsed(LinkTokenStatus),
+    /// The token was used too late.
+    Expired,
+    /// The link already has `max_recipients` recipients.
+    LimitReached,
+}
+
+/// The outcome of confirming a link token.
+pub struct ConfirmedToken {
+    pub link_id: String,
+    pub recipient_id: Uuid,
+    pub outcome: Confirmation,
+    /// Only present when the owner of the link was asked for approval, a
+    /// token is used once so the owner is asked once.
+    pub new_approval_token: Option<String>,
+}
+
+/// Link tokens are single use, the first confirmation sets `confirmed_at`
+/// and later ones only report the current status.
+///
+/// Confirmed grants expire after `grant_validity_hours` of the link, or the
+/// application wide `grant_validity_hours` when the link doesn't set it.
+///
+/// When the link needs approval the owner is asked in the same transaction,
+/// a recipient is never left waiting for an owner who wasn't asked.
+#[tracing::instrument(
+    name = "Mark link_token as confirmed",
+    skip(link_token, pool, base_url)
+)]
+pub async fn confirm_recipient(
+    pool: &PgPool,
+    link_token: &str,
+    grant_validity_hours: Option<i32>,
+    base_url: &ApplicationBaseUrl,
+) -> Result<ConfirmedToken, ConfirmationError> {
+    let mut transaction = pool.begin().await?;
+
+    let token = sqlx::query!(
+        r#"
+    SELECT links_tokens.link_id, links_tokens.recepient_id,
+        links_tokens.status AS "status: LinkTokenStatus", links_tokens.confirmed_at,
+        links_tokens.expiration_date, links.requires_approval, links.max_recipients,
+        COALESCE(links.grant_validity_hours, $2) AS grant_validity_hours
+    FROM links_tokens
+    JOIN links ON links.id = links_tokens.link_id
+    WHERE links_tokens.link_token = $1
+    -- locking the link makes confirmations of the same link wait for each
+    -- other, so `max_recipients` can't be exceeded
+    FOR UPDATE OF links_tokens, links
+            "#,
+        link_token,
+        grant_validity_hours
     )
-    .fetch_one(pool)
+    .fetch_optional(&mut *transaction)
     .await?
-    .link_id;
+    .ok_or(ConfirmationError::UnknownToken)?;
+
+    let confirmed_token = |outcome| ConfirmedToken {
+        link_id: token.link_id.clone(),
+        recipient_id: token.recepient_id,
+        outcome,
+        new_approval_token: None,
+    };
+
+    if token.confirmed_at.is_some() || token.status != LinkTokenStatus::Pending {
+        return Ok(confirmed_token(Confirmation::AlreadyUsed(token.status)));
+    }
+
+    if token
+        .expiration_date
+        .is_some_and(|expiration_date| expiration_date <= Utc::now())
+    {
+        return Ok(confirmed_token(Confirmation::Expired));
+    }
+
+    if let Some(max_recipients) = token.max_recipients
+        && count_other_recipients(&mut *transaction, &token.link_id, token.recepient_id).await?
+            >= i64::from(max_recipients)
+    {
+        return Ok(confirmed_token(Confirmation::LimitReached));
+    }
+
+    // from now on the expiration date is when the access of the recipient
+    // runs out, it starts once the owner approves when approval is needed
+    let (status, new_approval_token, expiration_date) = if token.requires_approval {
+        (
+            LinkTokenStatus::AwaitingApproval,
+            Some(generate_token(LINK_TOKEN_LENGTH)),
+            None,
+        )
+    } else {
+        let expiration_date = token
+            .grant_validity_hours
+            .map(|hours| Utc::now() + chrono::Duration::hours(hours.into()));
+        (LinkTokenStatus::Confirmed, None, expiration_date)
+    };
+
+    sqlx::query!(
+        r#"
+    UPDATE links_tokens SET status = $1, approval_token = $2, expiration_date = $3,
+        confirmed_at = now()
+    WHERE link_token = $4
+            "#,
+        status as LinkTokenStatus,
+        new_approval_token,
+        expiration_date,
+        link_token
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    if let Some(approval_token) = &new_approval_token {
+        send_approval_request_email(&mut transaction, base_url, approval_token).await?;
+    }
+
+    transaction.commit().await?;
 
-    Ok(link)
+    Ok(ConfirmedToken {
+        new_approval_token,
+        ..confirmed_token(Confirmation::Confirmed(status))
+    })
 }
@@ -3,58 +3,154 @@ use std::sync::Arc;
 use axum::{
     Form,
     extract::{Path, State},
+    http::HeaderMap,
     response::{Html, IntoResponse, Response},
 };
-use base64::{Engine, engine::general_purpose};
-use chrono::Utc;
-use rand::Rng;
-use reqwest::{StatusCode, Url};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use reqwest::StatusCode;
 use rinja_axum::Template;
 use serde::{Deserialize, Serialize};
+use serde_aux::field_attributes::deserialize_option_number_from_string;
+use sqlx::{Connection, Executor, PgPool, Postgres, Transaction};
 
-use crate::startup::AppState;
+use crate::{
+    configuration::SignInMode,
+    domain::{AllowlistEntry, LinkAlias, NormalizedUrl, RecipientEmail, TargetUrl},
+    routes::{
+        ApiKey, LinkEvent, LinkEventKind, create_owner, generate_token, get_owner_by_token,
+        get_owner_email, record_event, redirect_remembered_recipient, replace_allowlist,
+        set_owner_email,
+    },
+    startup::AppState,
+};
 
-fn generate_id() -> String {
-    let random_number = rand::rng().random_range(0..u32::MAX);
-    general_purpose::URL_SAFE_NO_PAD.encode(random_number.to_string())
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-pub struct LinkId {
-    id: String,
-}
+/// How many ids are tried before giving up on creating a link.
+const MAX_ID_ATTEMPTS: usize = 3;
+const MAX_TITLE_LENGTH: usize = 200;
+const MAX_DESCRIPTION_LENGTH: usize = 2000;
 
 #[derive(Deserialize, Debug, Serialize, Template)]
 #[template(path = "get_link.html")]
 pub struct LinkTargetTemplate {
     pub id: String,
+    pub base_path: String,
+    /// Returning recipients ask for a sign-in link instead of typing their
+    /// name and email.
+    pub magic_link: bool,
 }
 
 #[derive(Deserialize, Debug, Serialize, Template)]
 #[template(path = "redirect.html")]
 pub struct LinkRedirectionTemplate {
     pub id: String,
+    pub short_url: String,
+    /// Only present when a new owner was created along with the link, it's
+    /// the one and only time the management token is shown.
+    pub management_token: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Serialize)]
+#[derive(Deserialize, Debug, Serialize, Default)]
 pub struct LinkTarget {
     pub target_url: String,
+    /// Custom short link id, a random one is generated when it's left empty.
+    pub alias: Option<String>,
+    /// Attach the new link to an already existing owner instead of creating
+    /// a new one.
+    pub management_token: Option<String>,
+    /// Emails and `@domains` allowed to register for the link, anyone can
+    /// register when it's left empty.
+    pub allowlist: Option<String>,
+    /// Where the owner gets notified, required to approve recipients.
+    /// Ignored with an api key, only the management token can change it.
+    pub owner_email: Option<String>,
+    /// Recipients need the approval of the owner on top of confirming their
+    /// email.
+    pub requires_approval: Option<bool>,
+    /// Override of `ApplicationSettings::link_token_validity_hours`.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub link_token_validity_hours: Option<i32>,
+    /// Override of `ApplicationSettings::grant_validity_hours`.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub grant_validity_hours: Option<i32>,
+    /// When the link becomes available, either RFC 3339, which the create
+    /// form converts the local time of the owner to, or a `YYYY-MM-DDTHH:MM`
+    /// without a timezone taken as UTC.
+    pub active_from: Option<String>,
+    /// When the link stops being available, same formats as `active_from`.
+    pub expires_at: Option<String>,
+    /// How many times recipients can be redirected to the target url.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub max_clicks: Option<i32>,
+    /// How many distinct recipients can get access to the link.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub max_recipients: Option<i32>,
+    /// Hand back an existing link of the same owner when it has the same
+    /// target url and access policy, instead of creating a new one.
+    pub dedupe: Option<bool>,
+    /// Show recipients where they are going before redirecting them.
+    pub show_preview: Option<bool>,
+    /// Shown to recipients on the preview page.
+    pub title: Option<String>,
+    /// Notes of the owner, shown to recipients on the preview page.
+    pub description: Option<String>,
+}
+
+/// Access policy of a link chosen by its owner at creation time.
+#[derive(Default)]
+pub struct LinkOptions {
+    pub requires_approval: bool,
+    pub link_token_validity_hours: Option<i32>,
+    pub grant_validity_hours: Option<i32>,
+    pub active_from: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_clicks: Option<i32>,
+    pub max_recipients: Option<i32>,
+    pub show_preview: bool,
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+/// A row of the `links` table.
+pub struct StoredLink {
+    pub id: String,
+    pub target_url: String,
+    pub disabled: bool,
+    pub requires_approval: bool,
+    pub link_token_validity_hours: Option<i32>,
+    pub active_from: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_clicks: Option<i32>,
+    pub max_recipients: Option<i32>,
+    pub click_count: i32,
+    pub show_preview: bool,
+    pub title: Option<String>,
+    pub description: Option<String>,
 }
 
 #[tracing::instrument(
     name = "Redirecting to the target url link",
-    skip(requested_link, app_state)
+    skip(requested_link, app_state, headers)
 )]
 pub async fn link_access_page(
     State(app_state): State<Arc<AppState>>,
     Path(requested_link): Path<String>,
+    headers: HeaderMap,
 ) -> Result<impl IntoResponse, LinkError> {
-    let link = sqlx::query_as!(LinkId, "SELECT id FROM links WHERE id = $1", requested_link)
-        .fetch_optional(&app_state.pool)
-        .await?
-        .ok_or(LinkError::LinkNotFound)?;
-    let template = LinkTargetTemplate { id: link.id };
-    Ok(Html(template.render().unwrap()))
+    let link = get_available_link(&app_state.pool, &requested_link).await?;
+    record_event(
+        &app_state.pool,
+        LinkEvent::new(&link.id, LinkEventKind::View, &headers),
+    );
+    // verified recipients remembered by their browser skip the form
+    if let Some(redirect) = redirect_remembered_recipient(&app_state, &link, &headers).await? {
+        return Ok(redirect);
+    }
+    let template = LinkTargetTemplate {
+        id: link.id,
+        base_path: app_state.base_url.base_path().to_string(),
+        magic_link: app_state.sign_in_mode == SignInMode::MagicLink,
+    };
+    Ok(Html(template.render().unwrap()).into_response())
 }
 
 #[tracing::instrument(name = "Creating a new link", skip(new_link, app_state))]
@@ -62,33 +158,398 @@ pub async fn create_link(
     State(app_state): State<Arc<AppState>>,
     Form(new_link): Form<LinkTarget>,
 ) -> Result<impl IntoResponse, LinkError> {
-    let target_url = Url::parse(&new_link.target_url)
-        .map_err(|_| LinkError::InvalidUrl(new_link.target_url))?
-        .to_string();
-
-    #[allow(clippy::never_loop)]
-    for _ in 1..=3 {
-        let new_link_id = generate_id();
-        let time_now = Utc::now();
-        sqlx::query!(
-            r#"
-                    insert into links(id, target_url, created_at)
-                    values ($1, $2, $3)
-                "#,
+    let new_link = save_new_link(&app_state, new_link, None).await?;
+    let template = LinkRedirectionTemplate {
+        short_url: app_state.base_url.short_link(&new_link.id).to_string(),
+        id: new_link.id,
+        management_token: new_link.management_token,
+    };
+    Ok(template.render().unwrap())
+}
+
+/// A link that was just created.
+pub struct NewLink {
+    pub id: String,
+    /// Only present when a new owner was created along with the link.
+    pub management_token: Option<String>,
+}
+
+/// Validate and store a new link, shared by the html form and the json api.
+///
+/// Links created with an api key belong to the owner of the key and remember
+/// which key created them.
+pub async fn save_new_link(
+    app_state: &AppState,
+    new_link: LinkTarget,
+    api_key: Option<&ApiKey>,
+) -> Result<NewLink, LinkError> {
+    let target_url = TargetUrl::parse(&new_link.target_url, &app_state.target_url_policy)
+        .map_err(LinkError::InvalidUrl)?;
+    let normalized_target_url =
+        NormalizedUrl::parse(target_url.as_ref()).map_err(LinkError::InvalidUrl)?;
+    let allowlist = AllowlistEntry::parse_list(new_link.allowlist.as_deref().unwrap_or_default())
+        .map_err(LinkError::InvalidAllowlist)?;
+    let alias = new_link
+        .alias
+        .filter(|alias| !alias.trim().is_empty())
+        .map(LinkAlias::parse)
+        .transpose()
+        .map_err(LinkError::InvalidAlias)?;
+    // empty fields coming from the html form mean nothing was given
+    let owner_email = new_link
+        .owner_email
+        .filter(|email| !email.trim().is_empty())
+        .map(RecipientEmail::parse)
+        .transpose()
+        .map_err(LinkError::InvalidOwnerEmail)?;
+    let options = LinkOptions {
+        requires_approval: new_link.requires_approval.unwrap_or(false),
+        link_token_validity_hours: new_link
+            .link_token_validity_hours
+            .map(validity_hours)
+            .transpose()?,
+        grant_validity_hours: new_link
+            .grant_validity_hours
+            .map(validity_hours)
+            .transpose()?,
+        active_from: parse_optional_date(new_link.active_from)?,
+        expires_at: parse_optional_date(new_link.expires_at)?,
+        max_clicks: new_link.max_clicks.map(limit).transpose()?,
+        max_recipients: new_link.max_recipients.map(limit).transpose()?,
+        show_preview: new_link.show_preview.unwrap_or(false),
+        title: preview_text(new_link.title, "title", MAX_TITLE_LENGTH)?,
+        description: preview_text(new_link.description, "description", MAX_DESCRIPTION_LENGTH)?,
+    };
+    if let (Some(active_from), Some(expires_at)) = (options.active_from, options.expires_at)
+        && expires_at <= active_from
+    {
+        return Err(LinkError::InvalidDate(
+            "the link has to expire after it becomes available".to_string(),
+        ));
+    }
+
+    let mut transaction = app_state.pool.begin().await?;
+
+    let management_token = new_link
+        .management_token
+        .filter(|token| !token.trim().is_empty());
+    let (owner_id, management_token) = match (api_key, management_token) {
+        // a leaked key would otherwise redirect the approval requests of
+        // every link of the owner
+        (Some(api_key), _) => (api_key.owner_id, None),
+        (None, Some(token)) => {
+            let owner_id = get_owner_by_token(&app_state.pool, &token)
+                .await?
+                .ok_or(LinkError::Unauthorized)?;
+            if let Some(owner_email) = &owner_email {
+                set_owner_email(&mut transaction, owner_id, owner_email).await?;
+            }
+            (owner_id, None)
+        }
+        (None, None) => {
+            let token = generate_token(32);
+            let owner_id = create_owner(&mut transaction, &token, owner_email.as_ref()).await?;
+            (owner_id, Some(token))
+        }
+    };
+
+    // approval requests have to be sent somewhere
+    if options.requires_approval && get_owner_email(&mut transaction, owner_id).await?.is_none() {
+        return Err(LinkError::MissingOwnerEmail);
+    }
+
+    // a new owner has no links yet, and an alias always asks for a new link
+    if new_link.dedupe.unwrap_or(false)
+        && management_token.is_none()
+        && alias.is_none()
+        && let Some(id) = find_duplicate_link(
+            &mut transaction,
+            owner_id,
+            &normalized_target_url,
+            &options,
+            &allowlist,
+        )
+        .await?
+    {
+        transaction.commit().await?;
+        return Ok(NewLink {
+            id,
+            management_token,
+        });
+    }
+
+    if let Some(alias) = alias {
+        return match insert_link(
+            &mut transaction,
+            alias.as_ref(),
+            target_url.as_ref(),
+            &normalized_target_url,
+            owner_id,
+            api_key.map(|api_key| api_key.id),
+            &options,
+        )
+        .await
+        {
+            Ok(()) => {
+                replace_allowlist(&mut transaction, alias.as_ref(), &allowlist).await?;
+                transaction.commit().await?;
+                Ok(NewLink {
+                    id: alias.as_ref().to_owned(),
+                    management_token,
+                })
+            }
+            Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+                Err(LinkError::AliasTaken(alias.as_ref().to_owned()))
+            }
+            Err(e) => Err(e.into()),
+        };
+    }
+
+    for _ in 0..MAX_ID_ATTEMPTS {
+        let new_link_id = app_state.id_generator.generate(&mut transaction).await?;
+        // a unique violation aborts the whole transaction, only roll back to
+        // before the insert
+        let mut savepoint = Connection::begin(&mut *transaction).await?;
+        match insert_link(
+            &mut savepoint,
             &new_link_id,
-            &target_url,
-            &time_now
+            target_url.as_ref(),
+            &normalized_target_url,
+            owner_id,
+            api_key.map(|api_key| api_key.id),
+            &options,
         )
-        .execute(&app_state.pool)
-        .await?;
+        .await
+        {
+            Ok(()) => savepoint.commit().await?,
+            Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+                tracing::warn!("The id {} is already taken, retrying", new_link_id);
+                savepoint.rollback().await?;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        }
+        replace_allowlist(&mut transaction, &new_link_id, &allowlist).await?;
+
+        transaction.commit().await?;
 
-        let new_link = LinkRedirectionTemplate { id: new_link_id };
-        return Ok(new_link.render().unwrap());
+        return Ok(NewLink {
+            id: new_link_id,
+            management_token,
+        });
     }
 
     Err(LinkError::GenerateUniqueId)
 }
 
+/// Parse a date coming from the create form, an empty field means no date.
+fn parse_optional_date(date: Option<String>) -> Result<Option<DateTime<Utc>>, LinkError> {
+    let Some(date) = date.filter(|date| !date.trim().is_empty()) else {
+        return Ok(None);
+    };
+    if let Ok(date) = DateTime::parse_from_rfc3339(&date) {
+        return Ok(Some(date.with_timezone(&Utc)));
+    }
+    // the raw value of a `datetime-local` input, sent when js is off, has no
+    // timezone and may or may not have seconds
+    ["%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(&date, format).ok())
+        .map(|date| Some(date.and_utc()))
+        .ok_or_else(|| LinkError::InvalidDate(format!("{} is not a valid date", date)))
+}
+
+fn limit(limit: i32) -> Result<i32, LinkError> {
+    if limit > 0 {
+        Ok(limit)
+    } else {
+        Err(LinkError::InvalidLimit(limit))
+    }
+}
+
+/// Trim an optional preview text, an empty field means nothing was given.
+fn preview_text(
+    text: Option<String>,
+    field: &str,
+    max_length: usize,
+) -> Result<Option<String>, LinkError> {
+    let Some(text) = text
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+    else {
+        return Ok(None);
+    };
+    if text.chars().count() > max_length {
+        return Err(LinkError::InvalidPreview(format!(
+            "the {} can't be longer than {} characters",
+            field, max_length
+        )));
+    }
+    Ok(Some(text))
+}
+
+fn validity_hours(hours: i32) -> Result<i32, LinkError> {
+    if hours > 0 {
+        Ok(hours)
+    } else {
+        Err(LinkError::InvalidValidity(hours))
+    }
+}
+
+#[tracing::instrument(name = "Saving new link in the database", skip(transaction, options))]
+pub async fn insert_link(
+    transaction: &mut Transaction<'_, Postgres>,
+    link_id: &str,
+    target_url: &str,
+    normalized_target_url: &NormalizedUrl,
+    owner_id: uuid::Uuid,
+    api_key_id: Option<uuid::Uuid>,
+    options: &LinkOptions,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+    INSERT INTO links (id, target_url, created_at, owner_id, requires_approval,
+        link_token_validity_hours, grant_validity_hours, active_from, expires_at, max_clicks,
+        max_recipients, api_key_id, normalized_target_url, show_preview, title, description)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+        "#,
+        link_id,
+        target_url,
+        Utc::now(),
+        owner_id,
+        options.requires_approval,
+        options.link_token_validity_hours,
+        options.grant_validity_hours,
+        options.active_from,
+        options.expires_at,
+        options.max_clicks,
+        options.max_recipients,
+        api_key_id,
+        normalized_target_url.as_ref(),
+        options.show_preview,
+        options.title,
+        options.description
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+/// A link of the owner with the same normalized target url and the exact same
+/// access policy, disabled links are left out.
+#[tracing::instrument(name = "Find a duplicate link", skip(transaction, options, allowlist))]
+pub async fn find_duplicate_link(
+    transaction: &mut Transaction<'_, Postgres>,
+    owner_id: uuid::Uuid,
+    normalized_target_url: &NormalizedUrl,
+    options: &LinkOptions,
+    allowlist: &[AllowlistEntry],
+) -> Result<Option<String>, sqlx::Error> {
+    let mut allowlist: Vec<String> = allowlist.iter().map(ToString::to_string).collect();
+    allowlist.sort();
+    allowlist.dedup();
+    let link = sqlx::query!(
+        r#"
+    SELECT id FROM links
+    WHERE owner_id = $1 AND normalized_target_url = $2 AND NOT disabled
+        AND requires_approval = $3
+        AND link_token_validity_hours IS NOT DISTINCT FROM $4
+        AND grant_validity_hours IS NOT DISTINCT FROM $5
+        AND active_from IS NOT DISTINCT FROM $6
+        AND expires_at IS NOT DISTINCT FROM $7
+        AND max_clicks IS NOT DISTINCT FROM $8
+        AND max_recipients IS NOT DISTINCT FROM $9
+        AND show_preview = $11
+        AND title IS NOT DISTINCT FROM $12
+        AND description IS NOT DISTINCT FROM $13
+        AND ARRAY(
+            SELECT pattern FROM link_allowlist WHERE link_id = links.id ORDER BY pattern
+        ) = $10::text[]
+    ORDER BY created_at
+    LIMIT 1
+        "#,
+        owner_id,
+        normalized_target_url.as_ref(),
+        options.requires_approval,
+        options.link_token_validity_hours,
+        options.grant_validity_hours,
+        options.active_from,
+        options.expires_at,
+        options.max_clicks,
+        options.max_recipients,
+        &allowlist,
+        options.show_preview,
+        options.title,
+        options.description
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(link.map(|link| link.id))
+}
+
+/// Fetch a link that can currently be visited, disabled links and links
+/// outside of their activation window are reported as such rather than as
+/// missing.
+#[tracing::instrument(name = "Get an available link", skip(pool))]
+pub async fn get_available_link(pool: &PgPool, link_id: &str) -> Result<StoredLink, LinkError> {
+    let link = sqlx::query_as!(
+        StoredLink,
+        r#"
+    SELECT id, target_url, disabled, requires_approval, link_token_validity_hours, active_from,
+        expires_at, max_clicks, max_recipients, click_count, show_preview, title, description
+    FROM links WHERE id = $1
+        "#,
+        link_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(LinkError::LinkNotFound)?;
+
+    let now = Utc::now();
+    if link.disabled {
+        return Err(LinkError::LinkDisabled);
+    }
+    if let Some(active_from) = link.active_from.filter(|active_from| now < *active_from) {
+        return Err(LinkError::LinkNotYetActive(active_from));
+    }
+    if link.expires_at.is_some_and(|expires_at| expires_at <= now) {
+        return Err(LinkError::LinkExpired);
+    }
+    if link
+        .max_clicks
+        .is_some_and(|max_clicks| link.click_count >= max_clicks)
+    {
+        return Err(LinkError::LimitReached);
+    }
+    Ok(link)
+}
+
+/// Count a redirect to the target url, returns `None` once the click limit
+/// of the link is reached.
+///
+/// The limit is checked by the update itself, so concurrent redirects can't
+/// go over it.
+#[tracing::instrument(name = "Record a click on a link", skip(pool))]
+pub async fn record_click(pool: &PgPool, link_id: &str) -> Result<Option<String>, sqlx::Error> {
+    let link = sqlx::query!(
+        r#"
+    UPDATE links SET click_count = click_count + 1
+    WHERE id = $1 AND (max_clicks IS NULL OR click_count < max_clicks)
+    RETURNING target_url
+        "#,
+        link_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(link.map(|link| link.target_url))
+}
+
+#[derive(Template)]
+#[template(path = "link_error.html")]
+struct LinkErrorTemplate<'a> {
+    title: &'a str,
+    message: &'a str,
+}
+
 // TODO:
 // could be better, leave it for now
 #[derive(thiserror::Error, Debug)]
@@ -97,31 +558,187 @@ pub enum LinkError {
     GenerateUniqueId,
     #[error("couldn't insert new link to the database, sqlx error {0}")]
     SqlxError(#[from] sqlx::Error),
-    #[error("invalid url")]
+    #[error("invalid url, {0}")]
     InvalidUrl(String),
     #[error("link is not found in the db")]
     LinkNotFound,
+    #[error("link has been disabled by its owner")]
+    LinkDisabled,
+    #[error("invalid management token")]
+    Unauthorized,
+    #[error("invalid allowlist, {0}")]
+    InvalidAllowlist(String),
+    #[error("invalid owner email, {0}")]
+    InvalidOwnerEmail(String),
+    #[error("approving recipients requires an owner email")]
+    MissingOwnerEmail,
+    #[error("{0} is not a valid number of hours")]
+    InvalidValidity(i32),
+    #[error("invalid date, {0}")]
+    InvalidDate(String),
+    #[error("link is not available before {0}")]
+    LinkNotYetActive(DateTime<Utc>),
+    #[error("link has expired")]
+    LinkExpired,
+    #[error("{0} is not a valid limit")]
+    InvalidLimit(i32),
+    #[error("link has reached its limit")]
+    LimitReached,
+    #[error("invalid alias, {0}")]
+    InvalidAlias(String),
+    #[error("the alias {0} is already taken")]
+    AliasTaken(String),
+    #[error("invalid preview, {0}")]
+    InvalidPreview(String),
 }
 impl IntoResponse for LinkError {
     fn into_response(self) -> Response {
         match self {
-            LinkError::InvalidUrl(s) => {
-                tracing::error!("{}", LinkError::InvalidUrl(s));
-                StatusCode::BAD_REQUEST
+            LinkError::InvalidUrl(e) => {
+                tracing::error!("{}", e);
+                let template = LinkErrorTemplate {
+                    title: "Invalid url",
+                    message: &e,
+                };
+                (StatusCode::BAD_REQUEST, Html(template.render().unwrap())).into_response()
             }
             LinkError::GenerateUniqueId => {
                 tracing::error!("{}", LinkError::GenerateUniqueId);
-                StatusCode::BAD_REQUEST
+                let template = LinkErrorTemplate {
+                    title: "Could not create the link",
+                    message: "No free short link id was found, please try again",
+                };
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Html(template.render().unwrap()),
+                )
+                    .into_response()
             }
             LinkError::SqlxError(e) => {
                 tracing::error!("{}", LinkError::SqlxError(e));
-                StatusCode::BAD_REQUEST
+                StatusCode::BAD_REQUEST.into_response()
             }
             LinkError::LinkNotFound => {
                 tracing::error!("{}", LinkError::LinkNotFound);
-                StatusCode::BAD_REQUEST
+                StatusCode::BAD_REQUEST.into_response()
+            }
+            LinkError::LinkDisabled => {
+                tracing::error!("{}", LinkError::LinkDisabled);
+                let template = LinkErrorTemplate {
+                    title: "Link disabled",
+                    message: "The owner of this link has disabled it",
+                };
+                (StatusCode::GONE, Html(template.render().unwrap())).into_response()
+            }
+            LinkError::Unauthorized => {
+                tracing::error!("{}", LinkError::Unauthorized);
+                let template = LinkErrorTemplate {
+                    title: "Unauthorized",
+                    message: "The management token does not match this link",
+                };
+                (StatusCode::UNAUTHORIZED, Html(template.render().unwrap())).into_response()
+            }
+            LinkError::InvalidAllowlist(e) => {
+                tracing::error!("{}", e);
+                let template = LinkErrorTemplate {
+                    title: "Invalid allowlist",
+                    message: &e,
+                };
+                (StatusCode::BAD_REQUEST, Html(template.render().unwrap())).into_response()
+            }
+            LinkError::InvalidOwnerEmail(e) => {
+                tracing::error!("{}", e);
+                let template = LinkErrorTemplate {
+                    title: "Invalid owner email",
+                    message: &e,
+                };
+                (StatusCode::BAD_REQUEST, Html(template.render().unwrap())).into_response()
+            }
+            LinkError::MissingOwnerEmail => {
+                tracing::error!("{}", LinkError::MissingOwnerEmail);
+                let template = LinkErrorTemplate {
+                    title: "Missing owner email",
+                    message: "Enter your email to get notified when a recipient asks for access",
+                };
+                (StatusCode::BAD_REQUEST, Html(template.render().unwrap())).into_response()
+            }
+            LinkError::InvalidValidity(hours) => {
+                tracing::error!("{}", LinkError::InvalidValidity(hours));
+                let template = LinkErrorTemplate {
+                    title: "Invalid validity",
+                    message: "Validity periods have to be a positive number of hours",
+                };
+                (StatusCode::BAD_REQUEST, Html(template.render().unwrap())).into_response()
+            }
+            LinkError::InvalidDate(e) => {
+                tracing::error!("{}", e);
+                let template = LinkErrorTemplate {
+                    title: "Invalid date",
+                    message: &e,
+                };
+                (StatusCode::BAD_REQUEST, Html(template.render().unwrap())).into_response()
+            }
+            LinkError::LinkNotYetActive(active_from) => {
+                tracing::error!("{}", LinkError::LinkNotYetActive(active_from));
+                let message = format!(
+                    "This link will be available from {}",
+                    active_from.format("%Y-%m-%d %H:%M UTC")
+                );
+                let template = LinkErrorTemplate {
+                    title: "Not yet available",
+                    message: &message,
+                };
+                (StatusCode::FORBIDDEN, Html(template.render().unwrap())).into_response()
+            }
+            LinkError::LinkExpired => {
+                tracing::error!("{}", LinkError::LinkExpired);
+                let template = LinkErrorTemplate {
+                    title: "Link expired",
+                    message: "This link is no longer available",
+                };
+                (StatusCode::GONE, Html(template.render().unwrap())).into_response()
+            }
+            LinkError::InvalidLimit(limit) => {
+                tracing::error!("{}", LinkError::InvalidLimit(limit));
+                let template = LinkErrorTemplate {
+                    title: "Invalid limit",
+                    message: "Limits have to be a positive number",
+                };
+                (StatusCode::BAD_REQUEST, Html(template.render().unwrap())).into_response()
+            }
+            LinkError::LimitReached => {
+                tracing::error!("{}", LinkError::LimitReached);
+                let template = LinkErrorTemplate {
+                    title: "Limit reached",
+                    message: "This link has reached its limit and can't be accessed anymore",
+                };
+                (StatusCode::FORBIDDEN, Html(template.render().unwrap())).into_response()
+            }
+            LinkError::InvalidAlias(e) => {
+                tracing::error!("{}", e);
+                let template = LinkErrorTemplate {
+                    title: "Invalid alias",
+                    message: &e,
+                };
+                (StatusCode::BAD_REQUEST, Html(template.render().unwrap())).into_response()
+            }
+            LinkError::AliasTaken(alias) => {
+                tracing::error!("{}", LinkError::AliasTaken(alias.clone()));
+                let message = format!("/{} is already taken, choose another alias", alias);
+                let template = LinkErrorTemplate {
+                    title: "Alias taken",
+                    message: &message,
+                };
+                (StatusCode::CONFLICT, Html(template.render().unwrap())).into_response()
+            }
+            LinkError::InvalidPreview(e) => {
+                tracing::error!("{}", e);
+                let template = LinkErrorTemplate {
+                    title: "Invalid preview",
+                    message: &e,
+                };
+                (StatusCode::BAD_REQUEST, Html(template.render().unwrap())).into_response()
             }
         }
-        .into_response()
     }
 }
@@ -1,11 +1,29 @@
+mod api;
+mod api_keys;
 mod health_check;
 mod index;
+mod link_analytics;
+mod link_approvals;
+mod link_events;
+mod link_management;
 mod link_recipients;
 mod link_tokens_confrim;
 mod links;
+mod recipient_sessions;
+mod sign_in;
+mod tokens;
 
+pub use api::*;
+pub use api_keys::*;
 pub use health_check::*;
 pub use index::*;
+pub use link_analytics::*;
+pub use link_approvals::*;
+pub use link_events::*;
+pub use link_management::*;
 pub use link_recipients::*;
 pub use link_tokens_confrim::*;
 pub use links::*;
+pub use recipient_sessions::*;
+pub use sign_in::*;
+pub use tokens::*;
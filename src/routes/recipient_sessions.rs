@@ -0,0 +1,120 @@
+use anyhow::Context;
+use axum::http::HeaderMap;
+use axum_extra::extract::cookie::{Cookie, Key, SameSite, SignedCookieJar};
+use chrono::{DateTime, Utc};
+use secrecy::{ExposeSecret, SecretString};
+use uuid::Uuid;
+
+use crate::domain::ApplicationBaseUrl;
+
+const COOKIE_NAME: &str = "verified_recipient";
+
+/// Remembers verified recipients in a signed, http only cookie, so they
+/// don't have to type their name and email again on every visit.
+///
+/// The cookie only says who the recipient is, their access to a link is
+/// still checked on every visit. The expiry is signed along with the
+/// recipient id, an old cookie kept past its `Max-Age` is still refused.
+pub struct RecipientSessions {
+    key: Key,
+    validity: chrono::Duration,
+    secure: bool,
+}
+
+impl RecipientSessions {
+    pub fn new(
+        hmac_secret: &SecretString,
+        validity_hours: i64,
+        base_url: &ApplicationBaseUrl,
+    ) -> anyhow::Result<Self> {
+        let key = Key::try_from(hmac_secret.expose_secret().as_bytes())
+            .context("the hmac secret has to be at least 64 bytes long")?;
+        Ok(Self {
+            key,
+            validity: chrono::Duration::hours(validity_hours),
+            secure: base_url.is_https(),
+        })
+    }
+
+    /// The cookie jar of the request with the recipient remembered, to be
+    /// returned along with the response.
+    pub fn remember(&self, headers: &HeaderMap, recipient_id: Uuid) -> SignedCookieJar {
+        let expires_at = Utc::now() + self.validity;
+        let cookie = Cookie::build((
+            COOKIE_NAME,
+            format!("{}|{}", recipient_id, expires_at.timestamp()),
+        ))
+        .path("/")
+        .http_only(true)
+        .secure(self.secure)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(self.validity.num_seconds()));
+        SignedCookieJar::from_headers(headers, self.key.clone()).add(cookie)
+    }
+
+    /// The recipient remembered by the request, if its cookie is genuine and
+    /// has not expired.
+    pub fn recipient(&self, headers: &HeaderMap) -> Option<Uuid> {
+        let cookie = SignedCookieJar::from_headers(headers, self.key.clone()).get(COOKIE_NAME)?;
+        let (recipient_id, expires_at) = cookie.value().split_once('|')?;
+        let expires_at = DateTime::from_timestamp(expires_at.parse().ok()?, 0)?;
+        if expires_at <= Utc::now() {
+            return None;
+        }
+        recipient_id.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        http::{HeaderMap, HeaderValue, header},
+        response::IntoResponse,
+    };
+    use secrecy::SecretString;
+    use uuid::Uuid;
+
+    use crate::{domain::ApplicationBaseUrl, routes::RecipientSessions};
+
+    fn base_url() -> ApplicationBaseUrl {
+        ApplicationBaseUrl::parse("http://127.0.0.1").unwrap()
+    }
+
+    fn sessions(validity_hours: i64) -> RecipientSessions {
+        let secret = SecretString::from("a".repeat(64));
+        RecipientSessions::new(&secret, validity_hours, &base_url()).unwrap()
+    }
+
+    /// The `Cookie` header a browser would send back.
+    fn cookie_header(sessions: &RecipientSessions, recipient_id: Uuid) -> HeaderMap {
+        let response = sessions
+            .remember(&HeaderMap::new(), recipient_id)
+            .into_response();
+        let set_cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        let cookie = set_cookie.split(';').next().unwrap().to_string();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, HeaderValue::from_str(&cookie).unwrap());
+        headers
+    }
+
+    #[test]
+    fn a_remembered_recipient_is_recognized() {
+        let sessions = sessions(1);
+        let recipient_id = Uuid::new_v4();
+        let headers = cookie_header(&sessions, recipient_id);
+        assert_eq!(sessions.recipient(&headers), Some(recipient_id));
+    }
+
+    #[test]
+    fn an_expired_cookie_is_refused() {
+        let sessions = sessions(-1);
+        let headers = cookie_header(&sessions, Uuid::new_v4());
+        assert_eq!(sessions.recipient(&headers), None);
+    }
+
+    #[test]
+    fn short_secrets_are_refused() {
+        let secret = SecretString::from("too short");
+        assert!(RecipientSessions::new(&secret, 1, &base_url()).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use std::sync::Arc;
+
+use axum::{
+    Form,
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    response::{Html, IntoResponse, Redirect},
+};
+use chrono::{DateTime, Duration, Utc};
+use reqwest::StatusCode;
+use rinja_axum::Template;
+use serde::Deserialize;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    configuration::SignInMode,
+    domain::{ApplicationBaseUrl, LinkTokenStatus, RecipientEmail},
+    email_outbox::enqueue_email,
+    routes::{
+        LINK_TOKEN_LENGTH, RecipientError, check_status, cooldown_remaining, generate_token,
+        get_allowlist, get_available_link, hash_token, lock_recipient,
+    },
+    startup::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct SignInForm {
+    email: String,
+}
+
+#[derive(Deserialize)]
+pub struct SignInParameters {
+    token: String,
+}
+
+#[derive(Template)]
+#[template(path = "sign_in_sent.html")]
+struct SignInSentTemplate<'a> {
+    email: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "sign_in_expired.html")]
+struct SignInExpiredTemplate {
+    base_path: String,
+}
+
+/// Email a one time sign-in link to a recipient who already has access to
+/// the link, in the `magic_link` sign-in mode.
+///
+/// The answer is the same whether the email has access or not, so the form
+/// can't be used to find out who has. The email goes through the outbox and
+/// a recipient gets at most one per `resend_cooldown_seconds`, without being
+/// told, so neither the timing nor the answer gives them away.
+#[tracing::instrument(name = "Request a sign-in link", skip(form, app_state))]
+pub async fn request_sign_in(
+    State(app_state): State<Arc<AppState>>,
+    Path(link_id): Path<String>,
+    Form(form): Form<SignInForm>,
+) -> Result<impl IntoResponse, RecipientError> {
+    if app_state.sign_in_mode != SignInMode::MagicLink {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    let email = RecipientEmail::parse(form.email).map_err(RecipientError::InvalidRecipient)?;
+    let template = SignInSentTemplate {
+        email: email.as_ref(),
+    }
+    .render()
+    .unwrap();
+    let link = get_available_link(&app_state.pool, &link_id).await?;
+
+    if let Some(recipient_id) = get_recipient_by_email(&app_state.pool, &email).await?
+        && check_status(&app_state.pool, recipient_id, &link.id).await?
+            == Some(LinkTokenStatus::Confirmed)
+    {
+        let allowlist = get_allowlist(&app_state.pool, &link.id).await?;
+        if allowlist.is_empty() || allowlist.iter().any(|entry| entry.allows(&email)) {
+            let mut transaction = app_state.pool.begin().await?;
+            lock_recipient(&mut transaction, recipient_id).await?;
+            let latest = latest_sign_in_token(&mut transaction, recipient_id, &link.id).await?;
+            if cooldown_remaining(latest, app_state.resend_cooldown_seconds).is_none() {
+                let token = generate_token(LINK_TOKEN_LENGTH);
+                store_sign_in_token(
+                    &mut *transaction,
+                    &token,
+                    recipient_id,
+                    &link.id,
+                    Duration::minutes(app_state.sign_in_token_validity_minutes),
+                )
+                .await?;
+                send_sign_in_email(&mut transaction, &email, &app_state.base_url, &token).await?;
+            }
+            transaction.commit().await?;
+        }
+    }
+
+    Ok(Html(template).into_response())
+}
+
+/// Use a sign-in link, the recipient is remembered by their browser and sent
+/// to the short link, which redirects them to the target url.
+#[tracing::instrument(
+    name = "Sign in with a sign-in link",
+    skip(parameters, app_state, headers)
+)]
+pub async fn sign_in(
+    State(app_state): State<Arc<AppState>>,
+    parameters: Query<SignInParameters>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, RecipientError> {
+    let Some(sign_in) = use_sign_in_token(&app_state.pool, &parameters.token).await? else {
+        let template = SignInExpiredTemplate {
+            base_path: app_state.base_url.base_path().to_string(),
+        };
+        return Ok((StatusCode::GONE, Html(template.render().unwrap())).into_response());
+    };
+    let cookies = app_state
+        .recipient_sessions
+        .remember(&headers, sign_in.recipient_id);
+    let short_link = app_state.base_url.short_link(&sign_in.link_id);
+    Ok((cookies, Redirect::to(short_link.as_str())).into_response())
+}
+
+#[tracing::instrument(name = "Get a recipient by email", skip(pool, email))]
+pub async fn get_recipient_by_email(
+    pool: &PgPool,
+    email: &RecipientEmail,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let recipient = sqlx::query!(
+        "SELECT id FROM link_recipients WHERE email = $1",
+        email.as_ref()
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(recipient.map(|recipient| recipient.id))
+}
+
+#[tracing::instrument(name = "Get the latest sign-in token", skip(transaction))]
+async fn latest_sign_in_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    recipient_id: Uuid,
+    link_id: &str,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    let record = sqlx::query!(
+        r#"
+    SELECT max(created_at) AS latest FROM sign_in_tokens
+    WHERE recipient_id = $1 AND link_id = $2
+        "#,
+        recipient_id,
+        link_id
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(record.latest)
+}
+
+#[tracing::instrument(name = "Store a sign-in token", skip(executor, token))]
+pub async fn store_sign_in_token<'c>(
+    executor: impl Executor<'c, Database = Postgres>,
+    token: &str,
+    recipient_id: Uuid,
+    link_id: &str,
+    validity: Duration,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+    sqlx::query!(
+        r#"
+    INSERT INTO sign_in_tokens (token_hash, recipient_id, link_id, expires_at, created_at)
+    VALUES ($1, $2, $3, $4, $5)
+        "#,
+        hash_token(token),
+        recipient_id,
+        link_id,
+        now + validity,
+        now
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+pub struct SignIn {
+    pub recipient_id: Uuid,
+    pub link_id: String,
+}
+
+/// Sign-in tokens are single use, the update only finds a token that is
+/// unused and still valid, so using it twice at once lets only one through.
+#[tracing::instrument(name = "Use a sign-in token", skip(pool, token))]
+pub async fn use_sign_in_token(pool: &PgPool, token: &str) -> Result<Option<SignIn>, sqlx::Error> {
+    sqlx::query_as!(
+        SignIn,
+        r#"
+    UPDATE sign_in_tokens SET used_at = now()
+    WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+    RETURNING recipient_id, link_id
+        "#,
+        hash_token(token)
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Queued in the outbox along with the sign-in token.
+#[tracing::instrument(
+    name = "Send a sign-in email to a recipient",
+    skip(transaction, recipient_email, base_url, token)
+)]
+pub async fn send_sign_in_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    recipient_email: &RecipientEmail,
+    base_url: &ApplicationBaseUrl,
+    token: &str,
+) -> Result<(), sqlx::Error> {
+    let sign_in_link = base_url.sign_in_link(token);
+    let plain_body = format!(
+        "Visit {} to sign in and open your link.\nThe link works once and expires soon, ignore this email if you didn't ask for it.",
+        sign_in_link
+    );
+    let html_body = format!(
+        "Click <a href=\"{}\">here</a> to sign in and open your link.<br />The link works once and expires soon, ignore this email if you didn't ask for it.",
+        sign_in_link
+    );
+    enqueue_email(
+        transaction,
+        recipient_email,
+        "Your sign-in link",
+        &html_body,
+        &plain_body,
+    )
+    .await
+}
@@ -0,0 +1,20 @@
+use rand::{Rng, distr::Alphanumeric, rng};
+use sha2::{Digest, Sha256};
+
+/// A random alphanumeric token, used for link tokens, sign-in tokens,
+/// management tokens and api keys.
+pub fn generate_token(length: usize) -> String {
+    let mut rng = rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(length)
+        .collect()
+}
+
+/// Secret tokens are never stored as is, only their sha256 digest.
+///
+/// A fast hash is fine here as the tokens are long and random, unlike a
+/// password they can't be brute forced from a dictionary.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
@@ -14,43 +14,86 @@ use tracing::{Span, info, info_span};
 use uuid::Uuid;
 
 use crate::{
-    configuration::{DatabaseSettings, Settings},
-    email_client::EmailClient,
+    configuration::{
+        AfterConfirmation, ApplicationSettings, DatabaseSettings, Settings, SignInMode,
+    },
+    domain::{ApplicationBaseUrl, TargetUrlPolicy},
+    email_outbox::run_worker_until_stopped,
+    id_generator::IdGenerator,
     routes::{
-        access_link, add_recipient, confirm, create_link, health_check, index, link_access_page,
+        RecipientSessions, access_link, add_recipient, api_v1_router, approve, confirm,
+        continue_to_target, create_link, deny, disable_link, enable_link, health_check, index,
+        link_access_page, link_analytics, manage_link, request_sign_in, resend_confirmation,
+        sign_in, update_allowlist, update_link_target,
     },
 };
 
-pub struct ApplicationBaseUrl(pub String);
-
 pub struct AppState {
     pub pool: PgPool,
-    pub email_client: EmailClient,
     pub base_url: ApplicationBaseUrl,
+    pub link_token_validity_hours: i32,
+    pub resend_cooldown_seconds: i64,
+    pub grant_validity_hours: Option<i32>,
+    pub id_generator: IdGenerator,
+    pub target_url_policy: TargetUrlPolicy,
+    pub recipient_sessions: RecipientSessions,
+    pub after_confirmation: AfterConfirmation,
+    pub sign_in_mode: SignInMode,
+    pub sign_in_token_validity_minutes: i64,
 }
 
 pub async fn run(
     listener: TcpListener,
     pool: PgPool,
-    email_client: EmailClient,
-    base_url: String,
+    application_settings: ApplicationSettings,
 ) -> anyhow::Result<Serve<TcpListener, Router, Router>> {
-    // Wrapped in an Arc pointer to allow cheap cloning of AppState across handlers.
-    // This prevents unnecessary cloning of EmailClient, which has two String fields,
+    // Wrapped in an Arc pointer to allow cheap cloning of AppState across handlers,
     // since cloning an Arc is negligible.
+    let target_url_policy = TargetUrlPolicy::new(
+        application_settings.base_url.as_ref(),
+        application_settings.blocklist()?,
+    );
+    let recipient_sessions = RecipientSessions::new(
+        &application_settings.hmac_secret,
+        application_settings.recipient_session_hours,
+        &application_settings.base_url,
+    )?;
+    let id_generator =
+        IdGenerator::new(&application_settings.id_generator).map_err(anyhow::Error::msg)?;
     let app_state = Arc::new(AppState {
         pool,
-        email_client,
-        base_url: ApplicationBaseUrl(base_url),
+        base_url: application_settings.base_url,
+        link_token_validity_hours: application_settings.link_token_validity_hours,
+        resend_cooldown_seconds: application_settings.resend_cooldown_seconds,
+        grant_validity_hours: application_settings.grant_validity_hours,
+        id_generator,
+        target_url_policy,
+        recipient_sessions,
+        after_confirmation: application_settings.after_confirmation,
+        sign_in_mode: application_settings.sign_in,
+        sign_in_token_validity_minutes: application_settings.sign_in_token_validity_minutes,
     });
     let app = Router::new()
         .route("/", get(index))
         .route("/health_check", get(health_check))
         .route("/create", post(create_link))
+        .route("/manage", post(manage_link))
+        .route("/manage/target", post(update_link_target))
+        .route("/manage/disable", post(disable_link))
+        .route("/manage/enable", post(enable_link))
+        .route("/manage/allowlist", post(update_allowlist))
+        .route("/manage/analytics", post(link_analytics))
+        .nest("/api/v1", api_v1_router())
         .route("/{id}", get(link_access_page))
+        .route("/{id}/continue", get(continue_to_target))
         .route("/link_recipients/{id}", post(add_recipient))
+        .route("/link_recipients/{id}/resend", post(resend_confirmation))
         .route("/get_link/{id}", post(access_link))
+        .route("/sign_in/{id}", post(request_sign_in))
+        .route("/sign_in/confirm", get(sign_in))
         .route("/link_recipients/confirm", get(confirm))
+        .route("/link_recipients/approve", get(approve))
+        .route("/link_recipients/deny", get(deny))
         .nest_service("/templates", ServeFile::new("templates/output.css"))
         .with_state(app_state)
         .layer(
@@ -87,17 +130,7 @@ impl Application {
     pub async fn build(configuration: Settings) -> anyhow::Result<Self> {
         let connection_pool = get_connection_pool(&configuration.database);
 
-        let sender_email = configuration
-            .email_client
-            .sender()
-            .expect("Invalid sender email address.");
-        let timeout = configuration.email_client.timeout();
-        let email_client = EmailClient::new(
-            sender_email,
-            configuration.email_client.base_url,
-            configuration.email_client.authorization_token,
-            timeout,
-        );
+        let email_client = configuration.email_client.client()?;
 
         let listener = TcpListener::bind(format!(
             "{}:{}",
@@ -106,14 +139,15 @@ impl Application {
         .await?;
         let port = listener.local_addr()?.port();
 
-        let server = run(
-            listener,
+        let server = run(listener, connection_pool.clone(), configuration.application).await?;
+
+        // the worker delivers what the handlers queued in the email outbox,
+        // it lives as long as the runtime
+        tokio::spawn(run_worker_until_stopped(
             connection_pool,
             email_client,
-            configuration.application.base_url,
-        )
-        .await
-        .unwrap();
+            configuration.email_outbox,
+        ));
 
         Ok(Self { server, port })
     }
@@ -0,0 +1,215 @@
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+
+use crate::helpers::{TestApp, spawn_app};
+
+async fn create_api_key(
+    app: &TestApp,
+    management_token: &str,
+    scopes: &[&str],
+) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/api/v1/api_keys", &app.address))
+        .header("X-Management-Token", management_token)
+        .json(&json!({ "name": "release pipeline", "scopes": scopes }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn post_link_with_key(app: &TestApp, key: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/api/v1/links", &app.address))
+        .bearer_auth(key)
+        .json(&json!({ "target_url": "https://releases.example.com/v1.0.0.tar.gz" }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn create_api_key_returns_the_key_once_and_stores_its_hash() {
+    // Arrange
+    let app = spawn_app().await;
+    let (_, management_token) = app.create_owned_link().await;
+
+    // Act
+    let response = create_api_key(&app, &management_token, &["links:create"]).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body: Value = response.json().await.unwrap();
+    let key = body["key"].as_str().unwrap();
+    let saved = sqlx::query!("SELECT name, key_hash, scopes FROM api_keys")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved api key.");
+    assert_eq!(saved.name, "release pipeline");
+    assert_eq!(saved.scopes, vec!["links:create"]);
+    assert_ne!(saved.key_hash, key);
+}
+
+#[tokio::test]
+async fn create_api_key_is_rejected_with_an_invalid_management_token_or_scope() {
+    // Arrange
+    let app = spawn_app().await;
+    let (_, management_token) = app.create_owned_link().await;
+
+    // Act
+    let wrong_token = create_api_key(&app, "definitely-not-the-token", &["links:create"]).await;
+    let wrong_scope = create_api_key(&app, &management_token, &["links:delete"]).await;
+
+    // Assert
+    assert_eq!(wrong_token.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(wrong_scope.status(), StatusCode::BAD_REQUEST);
+    let body: Value = wrong_scope.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_scope");
+}
+
+#[tokio::test]
+async fn links_created_with_an_api_key_are_attributed_to_the_key() {
+    // Arrange
+    let app = spawn_app().await;
+    let (first_link, management_token) = app.create_owned_link().await;
+    let created: Value = create_api_key(&app, &management_token, &["links:create"])
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // Act
+    let response = post_link_with_key(&app, created["key"].as_str().unwrap()).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body: Value = response.json().await.unwrap();
+    // the owner already has a management token
+    assert!(body["management_token"].is_null());
+    let saved = sqlx::query!(
+        r#"
+    SELECT links.api_key_id, links.owner_id, first_link.owner_id AS first_owner_id
+    FROM links, links AS first_link
+    WHERE links.id = $1 AND first_link.id = $2
+        "#,
+        body["id"].as_str().unwrap(),
+        first_link
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch saved link.");
+    assert_eq!(
+        saved.api_key_id.map(|id| id.to_string()),
+        created["id"].as_str().map(String::from)
+    );
+    assert_eq!(saved.owner_id, saved.first_owner_id);
+}
+
+#[tokio::test]
+async fn invalid_or_insufficient_api_keys_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let (_, management_token) = app.create_owned_link().await;
+    let read_only: Value = create_api_key(&app, &management_token, &["recipients:read"])
+        .await
+        .json()
+        .await
+        .unwrap();
+    let test_cases = vec![
+        (
+            "usk_definitely-not-a-key",
+            StatusCode::UNAUTHORIZED,
+            "invalid_api_key",
+        ),
+        (
+            read_only["key"].as_str().unwrap(),
+            StatusCode::FORBIDDEN,
+            "insufficient_scope",
+        ),
+    ];
+
+    for (key, status, code) in test_cases {
+        // Act
+        let response = post_link_with_key(&app, key).await;
+
+        // Assert
+        assert_eq!(response.status(), status, "unexpected status for {}", code);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], code);
+    }
+}
+
+#[tokio::test]
+async fn list_recipients_accepts_an_api_key_of_the_owner() {
+    // Arrange
+    let app = spawn_app().await;
+    let (link_id, management_token) = app.create_owned_link().await;
+    let (other_link_id, _) = app.create_owned_link().await;
+    let created: Value = create_api_key(&app, &management_token, &["recipients:read"])
+        .await
+        .json()
+        .await
+        .unwrap();
+    let key = created["key"].as_str().unwrap();
+    let list = |link_id: String| {
+        reqwest::Client::new()
+            .get(format!(
+                "{}/api/v1/links/{}/recipients",
+                &app.address, link_id
+            ))
+            .bearer_auth(key)
+            .send()
+    };
+
+    // Act
+    let own_link = list(link_id).await.unwrap();
+    let other_link = list(other_link_id).await.unwrap();
+
+    // Assert
+    assert_eq!(own_link.status(), StatusCode::OK);
+    assert_eq!(other_link.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn an_api_key_cant_change_the_owner_email() {
+    // Arrange
+    let app = spawn_app().await;
+    let created: Value = app
+        .post_api(
+            "/links",
+            &json!({
+                "target_url": "https://www.example.com",
+                "owner_email": "owner@example.com"
+            }),
+        )
+        .await
+        .json()
+        .await
+        .unwrap();
+    let management_token = created["management_token"].as_str().unwrap();
+    let body: Value = create_api_key(&app, management_token, &["links:create"])
+        .await
+        .json()
+        .await
+        .unwrap();
+    let key = body["key"].as_str().unwrap();
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/api/v1/links", &app.address))
+        .bearer_auth(key)
+        .json(&json!({
+            "target_url": "https://www.example.com/other",
+            "owner_email": "attacker@example.com"
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let owner = sqlx::query!("SELECT email FROM link_owners")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch the owner.");
+    assert_eq!(owner.email.as_deref(), Some("owner@example.com"));
+}
@@ -0,0 +1,248 @@
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use url_shortener_with_a_twist::domain::ApplicationBaseUrl;
+
+use crate::helpers::{spawn_app, spawn_app_with};
+
+#[tokio::test]
+async fn create_link_returns_201_with_the_new_link() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_api(
+            "/links",
+            &json!({ "target_url": "https://www.example.com", "max_clicks": 5 }),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body: Value = response.json().await.unwrap();
+    let id = body["id"].as_str().unwrap();
+    assert!(body["short_url"].as_str().unwrap().ends_with(id));
+    assert!(body["management_token"].as_str().is_some());
+    let saved = sqlx::query!("SELECT target_url, max_clicks FROM links WHERE id = $1", id)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved link.");
+    assert_eq!(saved.target_url, "https://www.example.com/");
+    assert_eq!(saved.max_clicks, Some(5));
+}
+
+#[tokio::test]
+async fn short_url_keeps_the_port_and_path_prefix_of_the_base_url() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.base_url = ApplicationBaseUrl::parse("https://example.com:8443/go").unwrap()
+    })
+    .await;
+
+    // Act
+    let response = app
+        .post_api(
+            "/links",
+            &json!({ "target_url": "https://www.example.com" }),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body["short_url"].as_str().unwrap(),
+        format!(
+            "https://example.com:8443/go/{}",
+            body["id"].as_str().unwrap()
+        )
+    );
+}
+
+#[tokio::test]
+async fn api_errors_are_structured_json() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            app.post_api("/links", &json!({ "target_url": "not a url" }))
+                .await,
+            StatusCode::BAD_REQUEST,
+            "invalid_url",
+        ),
+        (
+            app.post_api("/links", &json!({ "allowlist": "@example.com" }))
+                .await,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "invalid_body",
+        ),
+        (
+            reqwest::get(format!("{}/api/v1/links/does-not-exist", &app.address))
+                .await
+                .unwrap(),
+            StatusCode::NOT_FOUND,
+            "link_not_found",
+        ),
+        (
+            app.post_api(
+                "/confirmations",
+                &json!({ "link_token": "unknownUnknownUnknownUnkn" }),
+            )
+            .await,
+            StatusCode::NOT_FOUND,
+            "unknown_link_token",
+        ),
+        (
+            app.post_api("/confirmations", &json!({ "link_token": "unknown" }))
+                .await,
+            StatusCode::UNAUTHORIZED,
+            "malformed_link_token",
+        ),
+        (
+            app.post_api(
+                "/links",
+                &json!({ "target_url": "https://www.example.com", "alias": "create" }),
+            )
+            .await,
+            StatusCode::BAD_REQUEST,
+            "invalid_alias",
+        ),
+    ];
+
+    for (response, status, code) in test_cases {
+        // Assert
+        assert_eq!(response.status(), status, "unexpected status for {}", code);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], code);
+        assert!(body["error"]["message"].is_string());
+    }
+}
+
+#[tokio::test]
+async fn get_link_metadata_does_not_reveal_the_target_url() {
+    // Arrange
+    let app = spawn_app().await;
+    let created: Value = app
+        .post_api(
+            "/links",
+            &json!({ "target_url": "https://www.example.com", "requires_approval": false }),
+        )
+        .await
+        .json()
+        .await
+        .unwrap();
+    let id = created["id"].as_str().unwrap();
+
+    // Act
+    let response = reqwest::get(format!("{}/api/v1/links/{}", &app.address, id))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["id"], id);
+    assert_eq!(body["requires_approval"], false);
+    assert!(!body.to_string().contains("example.com"));
+}
+
+#[tokio::test]
+async fn a_recipient_can_register_and_confirm_through_the_api() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let created: Value = app
+        .post_api(
+            "/links",
+            &json!({ "target_url": "https://www.example.com" }),
+        )
+        .await
+        .json()
+        .await
+        .unwrap();
+    let id = created["id"].as_str().unwrap();
+    let management_token = created["management_token"].as_str().unwrap();
+
+    // Act
+    let registered = app
+        .post_api(
+            &format!("/links/{}/recipients", id),
+            &json!({ "name": "johnny", "email": "depp@yahoo.com" }),
+        )
+        .await;
+    app.wait_for_outbox().await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    let link_token = confirmation_links
+        .plain_text
+        .query_pairs()
+        .find(|(key, _)| key == "link_token")
+        .unwrap()
+        .1
+        .to_string();
+    let confirmed = app
+        .post_api("/confirmations", &json!({ "link_token": link_token }))
+        .await;
+    let recipients = reqwest::Client::new()
+        .get(format!("{}/api/v1/links/{}/recipients", &app.address, id))
+        .header("X-Management-Token", management_token)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(registered.status(), StatusCode::ACCEPTED);
+    let registered: Value = registered.json().await.unwrap();
+    assert_eq!(registered["status"], "email_sent");
+    assert_eq!(confirmed.status(), StatusCode::OK);
+    let confirmed: Value = confirmed.json().await.unwrap();
+    assert_eq!(confirmed["link_id"], id);
+    assert_eq!(confirmed["status"], "confirmed");
+    assert_eq!(recipients.status(), StatusCode::OK);
+    let recipients: Value = recipients.json().await.unwrap();
+    assert_eq!(recipients[0]["email"], "depp@yahoo.com");
+    assert_eq!(recipients[0]["status"], "confirmed");
+}
+
+#[tokio::test]
+async fn list_recipients_requires_the_management_token() {
+    // Arrange
+    let app = spawn_app().await;
+    let created: Value = app
+        .post_api(
+            "/links",
+            &json!({ "target_url": "https://www.example.com" }),
+        )
+        .await
+        .json()
+        .await
+        .unwrap();
+    let id = created["id"].as_str().unwrap();
+    let url = format!("{}/api/v1/links/{}/recipients", &app.address, id);
+
+    // Act
+    let without_token = reqwest::get(&url).await.unwrap();
+    let wrong_token = reqwest::Client::new()
+        .get(&url)
+        .header("X-Management-Token", "definitely-not-the-token")
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    for response in [without_token, wrong_token] {
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "unauthorized");
+    }
+}
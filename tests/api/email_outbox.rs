@@ -0,0 +1,99 @@
+use std::time::{Duration, Instant};
+
+use url_shortener_with_a_twist::routes::LinkTarget;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{FormData, spawn_app};
+
+#[tokio::test]
+async fn registration_does_not_wait_for_the_email_api() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(3)))
+        .mount(&app.email_server)
+        .await;
+    let links_body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        ..Default::default()
+    };
+    let (_, short_id) = app.post_links(links_body).await;
+
+    // Act
+    let started = Instant::now();
+    let response = reqwest::Client::new()
+        .post(format!("{}/link_recipients/{}", &app.address, short_id))
+        .form(&FormData {
+            name: Some("hamada"),
+            email: Some("hamada@yahoo.com"),
+        })
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let (_, response) = app.register().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let email =
+        sqlx::query!(r#"SELECT status::text AS "status!", attempts, sent_at FROM email_outbox"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(email.status, "sent");
+    assert_eq!(email.attempts, 2);
+    assert!(email.sent_at.is_some());
+}
+
+#[tokio::test]
+async fn emails_are_marked_as_failed_after_the_last_attempt() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        // `max_attempts` of the tests
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let (_, response) = app.register().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let email =
+        sqlx::query!(r#"SELECT status::text AS "status!", attempts, last_error FROM email_outbox"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(email.status, "failed");
+    assert_eq!(email.attempts, 3);
+    assert!(email.last_error.unwrap().contains("500"));
+}
@@ -2,13 +2,16 @@ use secrecy::SecretString;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use std::sync::LazyLock;
 use url_shortener_with_a_twist::{
-    configuration::{DatabaseSettings, get_configuration},
+    configuration::{DatabaseSettings, EmailTransportSettings, Settings, get_configuration},
     routes::LinkTarget,
     startup::{Application, get_connection_pool},
     telemetry::{get_subscriber, init_subscriber},
 };
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{method, path},
+};
 
 // Ensure that the `tracing` stack is only initialised once using `once_cell`
 static TRACING: LazyLock<()> = LazyLock::new(|| {
@@ -37,14 +40,79 @@ pub struct ConfirmationLinks {
     pub plain_text: reqwest::Url,
 }
 
+/// Approve and deny links embedded in the approval request sent to the owner.
+#[derive(Debug)]
+pub struct ApprovalLinks {
+    pub approve: reqwest::Url,
+    pub deny: reqwest::Url,
+}
+
 impl TestApp {
+    /// Register a recipient, the confirmation email is delivered by the
+    /// time it returns.
     pub async fn post_link_recipeints(
         &self,
         body: FormData<'_>,
         link_id: &str,
     ) -> reqwest::Response {
+        let response = reqwest::Client::new()
+            .post(format!("{}/link_recipients/{}", &self.address, link_id))
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        self.wait_for_outbox().await;
+        response
+    }
+
+    pub async fn post_resend(&self, email: &str, link_id: &str) -> reqwest::Response {
+        let response = reqwest::Client::new()
+            .post(format!(
+                "{}/link_recipients/{}/resend",
+                &self.address, link_id
+            ))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        self.wait_for_outbox().await;
+        response
+    }
+
+    /// Let the email server accept every email sent to it.
+    pub async fn accept_emails(&self) {
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&self.email_server)
+            .await;
+    }
+
+    /// Wait for the background worker to deliver, or give up on, every email
+    /// in the outbox.
+    pub async fn wait_for_outbox(&self) {
+        for _ in 0..500 {
+            let pending = sqlx::query!(
+                r#"SELECT count(*) AS "count!" FROM email_outbox WHERE status = 'pending'"#
+            )
+            .fetch_one(&self.db_pool)
+            .await
+            .expect("Failed to count the pending emails.")
+            .count;
+            if pending == 0 {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("The email outbox was not drained in time.");
+    }
+
+    /// Ask for the target url of a link the way the htmx form of
+    /// `get_link.html` does.
+    pub async fn post_get_link(&self, body: FormData<'_>, link_id: &str) -> reqwest::Response {
         reqwest::Client::new()
-            .post(&format!("{}/link_recipients/{}", &self.address, link_id))
+            .post(format!("{}/get_link/{}", &self.address, link_id))
+            .header("HX-Request", "true")
             .form(&body)
             .send()
             .await
@@ -53,7 +121,7 @@ impl TestApp {
 
     pub async fn post_links(&self, body: LinkTarget) -> (reqwest::Response, String) {
         let response = reqwest::Client::new()
-            .post(&format!("{}/create", &self.address))
+            .post(format!("{}/create", &self.address))
             .form(&body)
             .send()
             .await
@@ -63,13 +131,98 @@ impl TestApp {
         if response.status() == reqwest::StatusCode::BAD_REQUEST {
             return (response, String::new());
         }
-        let short_id = sqlx::query!("SELECT id FROM links",)
+        let short_id = sqlx::query!("SELECT id FROM links ORDER BY created_at DESC LIMIT 1",)
             .fetch_one(&self.db_pool)
             .await
             .expect("Failed to fetch saved link.");
         (response, short_id.id)
     }
 
+    /// Create a link owned by a new owner, returning its short id and
+    /// management token.
+    pub async fn create_owned_link(&self) -> (String, String) {
+        let body = LinkTarget {
+            target_url: String::from("https://www.example.com"),
+            ..Default::default()
+        };
+        let (response, short_id) = self.post_links(body).await;
+        let management_token = extract_management_token(&response.text().await.unwrap());
+        (short_id, management_token)
+    }
+
+    /// Register hamada to a new link to https://www.example.com/, returning
+    /// the link id and the registration response.
+    pub async fn register(&self) -> (String, reqwest::Response) {
+        self.register_to(LinkTarget {
+            target_url: String::from("https://www.example.com"),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Register hamada to a new link created from `link`.
+    pub async fn register_to(&self, link: LinkTarget) -> (String, reqwest::Response) {
+        let (_, link_id) = self.post_links(link).await;
+        let body = FormData {
+            name: Some("hamada"),
+            email: Some("hamada@yahoo.com"),
+        };
+        let response = self.post_link_recipeints(body, &link_id).await;
+        (link_id, response)
+    }
+
+    /// A link to https://www.example.com/ hamada already confirmed, the
+    /// email server has to accept emails.
+    pub async fn link_with_a_verified_recipient(&self) -> String {
+        self.verify_recipient_on(LinkTarget {
+            target_url: String::from("https://www.example.com"),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Register hamada to a new link created from `link` and click on the
+    /// confirmation link, the emails it queues are delivered by the time it
+    /// returns.
+    pub async fn verify_recipient_on(&self, link: LinkTarget) -> String {
+        let (link_id, _) = self.register_to(link).await;
+        let email_request = &self.email_server.received_requests().await.unwrap()[0];
+        let confirmation_links = self.get_confirmation_links(email_request);
+        reqwest::get(confirmation_links.html)
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+        self.wait_for_outbox().await;
+        link_id
+    }
+
+    pub async fn post_manage<Body: serde::Serialize>(
+        &self,
+        action: &str,
+        body: &Body,
+    ) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/manage{}", &self.address, action))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_api<Body: serde::Serialize>(
+        &self,
+        route: &str,
+        body: &Body,
+    ) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/api/v1{}", &self.address, route))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     /// Extract the confirmation links embedded in the request to the email API.
     pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
         let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
@@ -93,6 +246,29 @@ impl TestApp {
         let plain_text = get_link(body["TextBody"].as_str().unwrap());
         ConfirmationLinks { html, plain_text }
     }
+
+    /// Extract the approve and deny links embedded in the approval request
+    /// sent to the owner of a link.
+    pub fn get_approval_links(&self, email_request: &wiremock::Request) -> ApprovalLinks {
+        let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+        let links: Vec<_> = linkify::LinkFinder::new()
+            .links(body["TextBody"].as_str().unwrap())
+            .filter(|l| *l.kind() == linkify::LinkKind::Url)
+            // the email also shows the short link the recipient asks for
+            .filter(|l| l.as_str().contains("approval_token="))
+            .map(|l| {
+                let mut link = reqwest::Url::parse(l.as_str()).unwrap();
+                assert_eq!(link.host_str().unwrap(), "127.0.0.1");
+                link.set_port(Some(self.port)).unwrap();
+                link
+            })
+            .collect();
+        assert_eq!(links.len(), 2);
+        ApprovalLinks {
+            approve: links[0].clone(),
+            deny: links[1].clone(),
+        }
+    }
 }
 
 #[derive(serde::Serialize)]
@@ -102,11 +278,41 @@ pub struct FormData<'a> {
 }
 
 #[derive(serde::Serialize)]
-pub struct FormDataLink<'a> {
-    pub link: Option<&'a str>,
+pub struct ManagementFormData<'a> {
+    pub link_id: &'a str,
+    pub management_token: &'a str,
+}
+
+/// Extract the one time management token from the html returned by `/create`.
+pub fn extract_management_token(html: &str) -> String {
+    let marker = "data-management-token=\"";
+    let start = html
+        .find(marker)
+        .expect("No management token in the response.")
+        + marker.len();
+    let end = start + html[start..].find('"').unwrap();
+    html[start..end].to_string()
+}
+
+/// The `verified_recipient=...` pair of the session cookie set by a response,
+/// ready to be sent back in a `Cookie` header.
+pub fn session_cookie(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get_all(reqwest::header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find(|value| value.starts_with("verified_recipient="))
+        .and_then(|value| value.split(';').next())
+        .map(str::to_string)
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Spawn the application with a tweaked configuration.
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
     LazyLock::force(&TRACING);
 
     // Launch a mock server to stand in for Postmark's API
@@ -119,8 +325,18 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         // Use a random OS port
         c.application.port = 0;
+        c.application.hmac_secret = SecretString::from("test-secret-".repeat(6));
         // Use the mock server as email API
-        c.email_client.base_url = email_server.uri();
+        c.email_client.transport = EmailTransportSettings::Postmark {
+            base_url: email_server.uri(),
+            authorization_token: SecretString::from("test-token"),
+        };
+        // deliver, and give up, quickly
+        c.email_outbox.poll_interval_milliseconds = 10;
+        c.email_outbox.max_attempts = 3;
+        c.email_outbox.retry_base_delay_milliseconds = 10;
+        c.email_outbox.retry_max_delay_milliseconds = 50;
+        configure(&mut c);
         c
     };
 
@@ -132,7 +348,7 @@ pub async fn spawn_app() -> TestApp {
         .await
         .expect("Failed to build application.");
     let application_port = application.port();
-    let _ = tokio::spawn(application.run_until_stopped());
+    tokio::spawn(application.run_until_stopped());
 
     TestApp {
         address: format!("http://127.0.0.1:{}", application_port),
@@ -0,0 +1,304 @@
+use reqwest::StatusCode;
+use url_shortener_with_a_twist::routes::LinkTarget;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{FormData, TestApp, spawn_app, spawn_app_with};
+
+/// Register hamada to a link requiring approval and click on the
+/// confirmation link, returning the short id.
+async fn confirmed_recipient_awaiting_approval(app: &TestApp) -> String {
+    app.accept_emails().await;
+    app.verify_recipient_on(LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        owner_email: Some(String::from("owner@example.com")),
+        requires_approval: Some(true),
+        ..Default::default()
+    })
+    .await
+}
+
+async fn status(app: &TestApp) -> String {
+    sqlx::query!(r#"SELECT status::text AS "status!" FROM links_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch the link token.")
+        .status
+}
+
+#[tokio::test]
+async fn create_link_requiring_approval_without_an_owner_email_returns_400() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let (response, _) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://www.example.com"),
+            requires_approval: Some(true),
+            ..Default::default()
+        })
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn confirming_an_email_asks_the_owner_for_approval() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    confirmed_recipient_awaiting_approval(&app).await;
+
+    // Assert
+    assert_eq!(status(&app).await, "awaiting_approval");
+    let requests = app.email_server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+    let body: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+    assert_eq!(body["To"], "owner@example.com");
+}
+
+#[tokio::test]
+async fn confirming_succeeds_when_the_approval_request_cant_be_sent_yet() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .up_to_n_times(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+    let (_, short_id) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://www.example.com"),
+            owner_email: Some(String::from("owner@example.com")),
+            requires_approval: Some(true),
+            ..Default::default()
+        })
+        .await;
+    app.post_link_recipeints(
+        FormData {
+            name: Some("hamada"),
+            email: Some("hamada@yahoo.com"),
+        },
+        &short_id,
+    )
+    .await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    // Act
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(status(&app).await, "awaiting_approval");
+    let queued = sqlx::query!(
+        "SELECT recipient FROM email_outbox WHERE subject = 'Someone asks to access your link'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("The approval request was not queued.");
+    assert_eq!(queued.recipient, "owner@example.com");
+}
+
+#[tokio::test]
+async fn recipients_awaiting_approval_are_not_redirected() {
+    // Arrange
+    let app = spawn_app().await;
+    let short_id = confirmed_recipient_awaiting_approval(&app).await;
+
+    // Act
+    let response = app
+        .post_get_link(
+            FormData {
+                name: Some("hamada"),
+                email: Some("hamada@yahoo.com"),
+            },
+            &short_id,
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(response.headers().get("HX-Redirect").is_none());
+}
+
+#[tokio::test]
+async fn approved_recipients_are_redirected_to_the_target() {
+    // Arrange
+    let app = spawn_app().await;
+    let short_id = confirmed_recipient_awaiting_approval(&app).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[1];
+    let approval_links = app.get_approval_links(email_request);
+
+    // Act
+    let response = reqwest::get(approval_links.approve).await.unwrap();
+    app.wait_for_outbox().await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(status(&app).await, "confirmed");
+    let response = app
+        .post_get_link(
+            FormData {
+                name: Some("hamada"),
+                email: Some("hamada@yahoo.com"),
+            },
+            &short_id,
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    // the recipient is told about the approval
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[2].body).unwrap();
+    assert_eq!(body["To"], "hamada@yahoo.com");
+}
+
+#[tokio::test]
+async fn approving_succeeds_when_the_recipient_cant_be_told_yet() {
+    // Arrange
+    let app = spawn_app().await;
+    confirmed_recipient_awaiting_approval(&app).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[1];
+    let approval_links = app.get_approval_links(email_request);
+    app.email_server.reset().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = reqwest::get(approval_links.approve).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(status(&app).await, "confirmed");
+    let queued = sqlx::query!(
+        "SELECT recipient FROM email_outbox WHERE subject = 'Your access request was approved'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("The approval email was not queued.");
+    assert_eq!(queued.recipient, "hamada@yahoo.com");
+}
+
+#[tokio::test]
+async fn denied_recipients_are_not_redirected() {
+    // Arrange
+    let app = spawn_app().await;
+    let short_id = confirmed_recipient_awaiting_approval(&app).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[1];
+    let approval_links = app.get_approval_links(email_request);
+
+    // Act
+    let response = reqwest::get(approval_links.deny).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(status(&app).await, "denied");
+    let response = app
+        .post_get_link(
+            FormData {
+                name: Some("hamada"),
+                email: Some("hamada@yahoo.com"),
+            },
+            &short_id,
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn denied_recipients_cant_register_again() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.resend_cooldown_seconds = 0).await;
+    let short_id = confirmed_recipient_awaiting_approval(&app).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[1];
+    let approval_links = app.get_approval_links(email_request);
+    reqwest::get(approval_links.deny).await.unwrap();
+
+    // Act
+    let response = app
+        .post_link_recipeints(
+            FormData {
+                name: Some("hamada"),
+                email: Some("hamada@yahoo.com"),
+            },
+            &short_id,
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains("denied your request")
+    );
+    let token_count = sqlx::query!(r#"SELECT count(*) AS "count!" FROM links_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(token_count, 1);
+    assert_eq!(status(&app).await, "denied");
+    let email_count = sqlx::query!(r#"SELECT count(*) AS "count!" FROM email_outbox"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(email_count, 2);
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn approval_links_can_only_be_used_once() {
+    // Arrange
+    let app = spawn_app().await;
+    confirmed_recipient_awaiting_approval(&app).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[1];
+    let approval_links = app.get_approval_links(email_request);
+
+    // Act
+    reqwest::get(approval_links.deny).await.unwrap();
+    let response = reqwest::get(approval_links.approve).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(status(&app).await, "denied");
+}
+
+#[tokio::test]
+async fn confirming_twice_asks_the_owner_once() {
+    // Arrange
+    let app = spawn_app().await;
+    confirmed_recipient_awaiting_approval(&app).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    // Act
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains("still has to approve")
+    );
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 2);
+    assert_eq!(status(&app).await, "awaiting_approval");
+}
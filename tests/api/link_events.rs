@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use crate::helpers::{FormData, TestApp, spawn_app};
+use url_shortener_with_a_twist::routes::LinkTarget;
+
+struct SavedEvent {
+    kind: String,
+    has_recipient: bool,
+    user_agent: Option<String>,
+    referrer: Option<String>,
+}
+
+/// Events are written in the background, wait until `count` of them landed.
+async fn wait_for_events(app: &TestApp, link_id: &str, count: usize) -> Vec<SavedEvent> {
+    for _ in 0..50 {
+        let events = sqlx::query!(
+            r#"
+    SELECT kind, recipient_id, user_agent, referrer FROM link_events
+    WHERE link_id = $1 ORDER BY created_at
+            "#,
+            link_id
+        )
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch link events.");
+        if events.len() >= count {
+            return events
+                .into_iter()
+                .map(|event| SavedEvent {
+                    kind: event.kind,
+                    has_recipient: event.recipient_id.is_some(),
+                    user_agent: event.user_agent,
+                    referrer: event.referrer,
+                })
+                .collect();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Less than {} events were recorded for {}.", count, link_id);
+}
+
+#[tokio::test]
+async fn link_access_page_records_a_view_with_the_request_headers() {
+    // Arrange
+    let app = spawn_app().await;
+    let (_, link_id) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://www.example.com"),
+            ..Default::default()
+        })
+        .await;
+
+    // Act
+    reqwest::Client::new()
+        .get(format!("{}/{}", &app.address, link_id))
+        .header("User-Agent", "integration-test")
+        .header("Referer", "https://chat.example.com/room")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    // Assert
+    let events = wait_for_events(&app, &link_id, 1).await;
+    assert_eq!(events[0].kind, "view");
+    assert!(!events[0].has_recipient);
+    assert_eq!(events[0].user_agent.as_deref(), Some("integration-test"));
+    assert_eq!(
+        events[0].referrer.as_deref(),
+        Some("https://chat.example.com/room")
+    );
+}
+
+#[tokio::test]
+async fn every_step_to_the_target_url_is_recorded() {
+    // Arrange
+    let app = spawn_app().await;
+    app.accept_emails().await;
+    let (_, link_id) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://www.example.com"),
+            ..Default::default()
+        })
+        .await;
+
+    // Act
+    reqwest::get(format!("{}/{}", &app.address, link_id))
+        .await
+        .unwrap();
+    wait_for_events(&app, &link_id, 1).await;
+    let body = FormData {
+        name: Some("johnny"),
+        email: Some("depp@yahoo.com"),
+    };
+    app.post_link_recipeints(body, &link_id).await;
+    wait_for_events(&app, &link_id, 2).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html).await.unwrap();
+    wait_for_events(&app, &link_id, 3).await;
+    app.post_get_link(
+        FormData {
+            name: Some("johnny"),
+            email: Some("depp@yahoo.com"),
+        },
+        &link_id,
+    )
+    .await;
+
+    // Assert
+    let events = wait_for_events(&app, &link_id, 4).await;
+    let kinds: Vec<_> = events.iter().map(|event| event.kind.as_str()).collect();
+    assert_eq!(
+        kinds,
+        vec!["view", "registration", "confirmation", "redirect"]
+    );
+    assert!(events[1..].iter().all(|event| event.has_recipient));
+}
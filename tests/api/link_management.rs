@@ -0,0 +1,316 @@
+use reqwest::StatusCode;
+use url_shortener_with_a_twist::routes::LinkTarget;
+
+use crate::helpers::{FormData, ManagementFormData, spawn_app};
+
+#[tokio::test]
+async fn create_link_stores_the_hash_of_the_management_token() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let (_, management_token) = app.create_owned_link().await;
+
+    // Assert
+    let saved = sqlx::query!(
+        "SELECT management_token_hash FROM link_owners JOIN links ON links.owner_id = link_owners.id"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch saved owner.");
+    assert!(!management_token.is_empty());
+    assert_ne!(saved.management_token_hash, management_token);
+}
+
+#[tokio::test]
+async fn manage_link_returns_200_for_the_owner() {
+    // Arrange
+    let app = spawn_app().await;
+    let (short_id, management_token) = app.create_owned_link().await;
+
+    // Act
+    let response = app
+        .post_manage(
+            "",
+            &ManagementFormData {
+                link_id: &short_id,
+                management_token: &management_token,
+            },
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains("https://www.example.com")
+    );
+}
+
+#[tokio::test]
+async fn management_endpoints_reject_an_invalid_token_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+    let (short_id, _) = app.create_owned_link().await;
+    let (_, other_owner_token) = app.create_owned_link().await;
+
+    for action in ["", "/disable", "/enable", "/analytics"] {
+        for management_token in ["definitely-not-the-token", other_owner_token.as_str()] {
+            // Act
+            let response = app
+                .post_manage(
+                    action,
+                    &ManagementFormData {
+                        link_id: &short_id,
+                        management_token,
+                    },
+                )
+                .await;
+
+            // Assert
+            assert_eq!(
+                response.status(),
+                StatusCode::UNAUTHORIZED,
+                "/manage{} did not reject an invalid token",
+                action
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn disabled_links_return_a_410() {
+    // Arrange
+    let app = spawn_app().await;
+    let (short_id, management_token) = app.create_owned_link().await;
+
+    // Act
+    app.post_manage(
+        "/disable",
+        &ManagementFormData {
+            link_id: &short_id,
+            management_token: &management_token,
+        },
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+    let response = reqwest::get(&format!("{}/{}", &app.address, short_id))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::GONE);
+}
+
+#[tokio::test]
+async fn enabling_a_disabled_link_makes_it_reachable_again() {
+    // Arrange
+    let app = spawn_app().await;
+    let (short_id, management_token) = app.create_owned_link().await;
+    let form = ManagementFormData {
+        link_id: &short_id,
+        management_token: &management_token,
+    };
+
+    // Act
+    app.post_manage("/disable", &form).await;
+    app.post_manage("/enable", &form).await;
+    let response = reqwest::get(&format!("{}/{}", &app.address, short_id))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn update_link_target_changes_the_target_url() {
+    // Arrange
+    let app = spawn_app().await;
+    let (short_id, management_token) = app.create_owned_link().await;
+
+    #[derive(serde::Serialize)]
+    struct UpdateTarget<'a> {
+        link_id: &'a str,
+        management_token: &'a str,
+        target_url: &'a str,
+    }
+
+    // Act
+    let response = app
+        .post_manage(
+            "/target",
+            &UpdateTarget {
+                link_id: &short_id,
+                management_token: &management_token,
+                target_url: "https://www.rust-lang.org",
+            },
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let saved = sqlx::query!("SELECT target_url FROM links WHERE id = $1", short_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved link.");
+    assert_eq!(saved.target_url, "https://www.rust-lang.org/");
+}
+
+#[tokio::test]
+async fn create_link_with_an_existing_token_keeps_the_same_owner() {
+    // Arrange
+    let app = spawn_app().await;
+    let (_, management_token) = app.create_owned_link().await;
+
+    // Act
+    let (response, short_id) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://www.rust-lang.org"),
+            management_token: Some(management_token.clone()),
+            ..Default::default()
+        })
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    // the token is only handed out once
+    assert!(
+        !response
+            .text()
+            .await
+            .unwrap()
+            .contains("data-management-token")
+    );
+    let owners = sqlx::query!("SELECT COUNT(*) as count FROM link_owners")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to count owners.");
+    assert_eq!(owners.count, Some(1));
+    let response = app
+        .post_manage(
+            "",
+            &ManagementFormData {
+                link_id: &short_id,
+                management_token: &management_token,
+            },
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn create_link_with_an_unknown_token_is_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/create", &app.address))
+        .form(&LinkTarget {
+            target_url: String::from("https://www.example.com"),
+            management_token: Some(String::from("not-a-real-token")),
+            ..Default::default()
+        })
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let links = sqlx::query!("SELECT COUNT(*) as count FROM links")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to count links.");
+    assert_eq!(links.count, Some(0));
+}
+
+#[tokio::test]
+async fn update_allowlist_replaces_the_allowlist_of_the_link() {
+    // Arrange
+    let app = spawn_app().await;
+    let (short_id, management_token) = app.create_owned_link().await;
+
+    #[derive(serde::Serialize)]
+    struct UpdateAllowlist<'a> {
+        link_id: &'a str,
+        management_token: &'a str,
+        allowlist: &'a str,
+    }
+
+    // Act
+    let response = app
+        .post_manage(
+            "/allowlist",
+            &UpdateAllowlist {
+                link_id: &short_id,
+                management_token: &management_token,
+                allowlist: "@OurCompany.com\nhamada@yahoo.com",
+            },
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let saved = sqlx::query!(
+        "SELECT pattern FROM link_allowlist WHERE link_id = $1 ORDER BY pattern",
+        short_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .expect("Failed to fetch the allowlist.");
+    let patterns: Vec<_> = saved.into_iter().map(|row| row.pattern).collect();
+    assert_eq!(patterns, vec!["@ourcompany.com", "hamada@yahoo.com"]);
+}
+
+#[tokio::test]
+async fn link_analytics_shows_event_counts_and_recipients_to_the_owner() {
+    // Arrange
+    let app = spawn_app().await;
+    app.accept_emails().await;
+    let (short_id, management_token) = app.create_owned_link().await;
+    app.post_link_recipeints(
+        FormData {
+            name: Some("johnny"),
+            email: Some("depp@yahoo.com"),
+        },
+        &short_id,
+    )
+    .await;
+    for kind in ["view", "view", "view", "redirect"] {
+        sqlx::query!(
+            r#"
+    INSERT INTO link_events (id, link_id, kind, created_at)
+    VALUES (gen_random_uuid(), $1, $2, now())
+            "#,
+            short_id,
+            kind
+        )
+        .execute(&app.db_pool)
+        .await
+        .expect("Failed to insert a link event.");
+    }
+
+    // Act
+    let response = app
+        .post_manage(
+            "/analytics",
+            &ManagementFormData {
+                link_id: &short_id,
+                management_token: &management_token,
+            },
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let html = response.text().await.unwrap();
+    assert!(html.contains(r#"<div class="stat-value">3</div>"#));
+    assert!(html.contains("3 views"));
+    assert!(html.contains("depp@yahoo.com"));
+    assert!(html.contains("pending"));
+}
@@ -1,4 +1,4 @@
-use crate::helpers::{FormData, spawn_app};
+use crate::helpers::{FormData, spawn_app, spawn_app_with};
 use url_shortener_with_a_twist::routes::LinkTarget;
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
@@ -14,6 +14,7 @@ async fn add_recipient_returns_a_200_for_valid_form_data() {
 
     let links_body = LinkTarget {
         target_url: String::from("https://www.example.com"),
+        ..Default::default()
     };
     let (_, short_id) = app.post_links(links_body).await;
 
@@ -41,6 +42,7 @@ async fn add_recipient_persists_the_new_recipient() {
 
     let links_body = LinkTarget {
         target_url: String::from("https://www.example.com"),
+        ..Default::default()
     };
     let (_, short_id) = app.post_links(links_body).await;
 
@@ -56,7 +58,7 @@ async fn add_recipient_persists_the_new_recipient() {
     // get status from links_tokens
     assert_eq!(saved.name, "hamada");
     assert_eq!(saved.email, "hamada@yahoo.com");
-    let query = sqlx::query!("SELECT status FROM links_tokens")
+    let query = sqlx::query!(r#"SELECT status::text AS "status!" FROM links_tokens"#)
         .fetch_one(&app.db_pool)
         .await
         .expect("Failed to fetch saved recipient.");
@@ -74,6 +76,7 @@ async fn add_recipient_sends_a_confirmation_email_for_valid_data() {
 
     let links_body = LinkTarget {
         target_url: String::from("https://www.example.com"),
+        ..Default::default()
     };
     let (_, short_id) = app.post_links(links_body).await;
 
@@ -102,6 +105,7 @@ async fn add_recipient_sends_a_confirmation_email_with_a_link() {
 
     let links_body = LinkTarget {
         target_url: String::from("https://www.example.com"),
+        ..Default::default()
     };
     let (_, short_id) = app.post_links(links_body).await;
 
@@ -151,14 +155,13 @@ async fn add_recipient_returns_a_422_when_data_is_missing() {
     ];
     let links_body = LinkTarget {
         target_url: String::from("https://www.example.com"),
+        ..Default::default()
     };
     let (_, short_id) = app.post_links(links_body).await;
 
     for (invalid_body, error_message) in test_cases {
         // Act
-        let response = app
-            .post_link_recipeints(invalid_body.into(), &short_id)
-            .await;
+        let response = app.post_link_recipeints(invalid_body, &short_id).await;
 
         // Assert
         assert_eq!(
@@ -201,6 +204,7 @@ async fn add_recipient_returns_a_400_when_fields_are_present_but_invalid() {
 
     let links_body = LinkTarget {
         target_url: String::from("https://www.example.com"),
+        ..Default::default()
     };
     let (_, short_id) = app.post_links(links_body).await;
 
@@ -217,3 +221,264 @@ async fn add_recipient_returns_a_400_when_fields_are_present_but_invalid() {
         );
     }
 }
+
+#[tokio::test]
+async fn add_recipient_returns_a_403_for_an_email_outside_the_allowlist() {
+    // Arrange
+    let app = spawn_app().await;
+    let links_body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        allowlist: Some(String::from("@ourcompany.com, depp@yahoo.com")),
+        ..Default::default()
+    };
+    let (_, short_id) = app.post_links(links_body).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_link_recipeints(
+            FormData {
+                name: Some("hamada"),
+                email: Some("hamada@yahoo.com"),
+            },
+            &short_id,
+        )
+        .await;
+
+    // Assert
+    assert_eq!(403, response.status().as_u16());
+    assert!(response.text().await.unwrap().contains("hamada@yahoo.com"));
+    let saved = sqlx::query!("SELECT COUNT(*) as count FROM link_recipients")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to count recipients.");
+    assert_eq!(saved.count, Some(0));
+}
+
+#[tokio::test]
+async fn add_recipient_accepts_emails_matching_the_allowlist() {
+    // Arrange
+    let app = spawn_app().await;
+    let links_body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        allowlist: Some(String::from("@ourcompany.com, depp@yahoo.com")),
+        ..Default::default()
+    };
+    let (_, short_id) = app.post_links(links_body).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    for email in ["hamada@OurCompany.com", "depp@yahoo.com"] {
+        // Act
+        let response = app
+            .post_link_recipeints(
+                FormData {
+                    name: Some("hamada"),
+                    email: Some(email),
+                },
+                &short_id,
+            )
+            .await;
+
+        // Assert
+        assert_eq!(
+            200,
+            response.status().as_u16(),
+            "{} was refused although it matches the allowlist",
+            email
+        );
+    }
+}
+
+#[tokio::test]
+async fn add_recipient_returns_a_403_once_max_recipients_is_reached() {
+    // Arrange
+    let app = spawn_app().await;
+    let links_body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        max_recipients: Some(1),
+        ..Default::default()
+    };
+    let (_, short_id) = app.post_links(links_body).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_link_recipeints(
+        FormData {
+            name: Some("johnny"),
+            email: Some("depp@yahoo.com"),
+        },
+        &short_id,
+    )
+    .await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Act
+    let response = app
+        .post_link_recipeints(
+            FormData {
+                name: Some("hamada"),
+                email: Some("hamada@yahoo.com"),
+            },
+            &short_id,
+        )
+        .await;
+
+    // Assert
+    assert_eq!(403, response.status().as_u16());
+    assert!(response.text().await.unwrap().contains("limit"));
+}
+
+#[tokio::test]
+async fn resending_sends_a_new_link_and_invalidates_the_old_one() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.resend_cooldown_seconds = 0).await;
+    app.accept_emails().await;
+    let (short_id, _) = app.register().await;
+
+    // Act
+    let response = app.post_resend("hamada@yahoo.com", &short_id).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    assert_eq!(email_requests.len(), 2);
+    let old_link = app.get_confirmation_links(&email_requests[0]).html;
+    let new_link = app.get_confirmation_links(&email_requests[1]).html;
+    assert_ne!(old_link, new_link);
+    assert_eq!(reqwest::get(old_link).await.unwrap().status().as_u16(), 410);
+    assert_eq!(reqwest::get(new_link).await.unwrap().status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn registering_again_before_confirming_rotates_the_link_token() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.resend_cooldown_seconds = 0).await;
+    app.accept_emails().await;
+    let (short_id, _) = app.register().await;
+
+    // Act
+    let response = app
+        .post_link_recipeints(
+            FormData {
+                name: Some("hamada"),
+                email: Some("hamada@yahoo.com"),
+            },
+            &short_id,
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let pending = sqlx::query!(
+        r#"
+    SELECT count(*) AS "count!" FROM links_tokens
+    WHERE status = 'pending' AND expiration_date > now()
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(pending.count, 1);
+}
+
+#[tokio::test]
+async fn resending_within_the_cooldown_is_refused() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.resend_cooldown_seconds = 60).await;
+    app.accept_emails().await;
+    let (short_id, _) = app.register().await;
+
+    // Act
+    let response = app.post_resend("hamada@yahoo.com", &short_id).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: i64 = response.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after));
+    let html = response.text().await.unwrap();
+    assert!(html.contains(&format!("try again in {} seconds", retry_after)));
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn resending_to_an_unregistered_email_returns_a_404() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.resend_cooldown_seconds = 0).await;
+    app.accept_emails().await;
+    let (short_id, _) = app.register().await;
+
+    // Act
+    let response = app.post_resend("someone@yahoo.com", &short_id).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn the_confirmation_email_follows_the_accept_language_of_the_registration() {
+    // Arrange
+    let app = spawn_app().await;
+    app.accept_emails().await;
+    let links_body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        description: Some(String::from("les photos du mariage")),
+        ..Default::default()
+    };
+    let (_, short_id) = app.post_links(links_body).await;
+
+    // Act
+    reqwest::Client::new()
+        .post(format!("{}/link_recipients/{}", &app.address, short_id))
+        .header("Accept-Language", "fr-FR,fr;q=0.9,en;q=0.8")
+        .form(&FormData {
+            name: Some("hamada"),
+            email: Some("hamada@yahoo.com"),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    app.wait_for_outbox().await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["Subject"], "Un ami veut vous montrer quelque chose !");
+    for field in ["HtmlBody", "TextBody"] {
+        let text = body[field].as_str().unwrap();
+        assert!(text.contains("Bonjour hamada,"));
+        assert!(text.contains("les photos du mariage"));
+        assert!(text.contains("127.0.0.1"));
+    }
+    let locale = sqlx::query!("SELECT locale FROM link_recipients")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .locale;
+    assert_eq!(locale, "fr");
+}
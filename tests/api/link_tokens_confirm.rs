@@ -1,5 +1,7 @@
-use crate::helpers::{FormData, spawn_app};
-use url_shortener_with_a_twist::routes::LinkTarget;
+use crate::helpers::{FormData, session_cookie, spawn_app, spawn_app_with};
+use url_shortener_with_a_twist::{
+    configuration::AfterConfirmation, domain::ApplicationBaseUrl, routes::LinkTarget,
+};
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
 
@@ -17,6 +19,31 @@ async fn confirmations_without_token_are_rejected_with_a_400() {
     assert_eq!(response.status().as_u16(), 400);
 }
 
+#[tokio::test]
+async fn unknown_and_malformed_confirmation_links_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        ("unknownUnknownUnknownUnkn", 404),
+        ("short", 401),
+        ("not-alphanumeric-but-25-c", 401),
+    ];
+
+    for (link_token, status) in test_cases {
+        // Act
+        let response = reqwest::get(&format!(
+            "{}/link_recipients/confirm?link_token={}",
+            app.address, link_token
+        ))
+        .await
+        .unwrap();
+
+        // Assert
+        assert_eq!(response.status().as_u16(), status, "for {}", link_token);
+        assert!(response.text().await.unwrap().contains("Invalid Link"));
+    }
+}
+
 #[tokio::test]
 async fn the_link_returned_by_add_recipient_returns_a_200_if_called() {
     // Arrange
@@ -34,6 +61,7 @@ async fn the_link_returned_by_add_recipient_returns_a_200_if_called() {
 
     let links_body = LinkTarget {
         target_url: String::from("https://www.example.com"),
+        ..Default::default()
     };
     let (_, short_id) = app.post_links(links_body).await;
 
@@ -65,6 +93,7 @@ async fn clicking_on_the_confirmation_link_confirms_a_recipient() {
 
     let links_body = LinkTarget {
         target_url: String::from("https://www.example.com"),
+        ..Default::default()
     };
     let (_, short_id) = app.post_links(links_body).await;
 
@@ -73,7 +102,7 @@ async fn clicking_on_the_confirmation_link_confirms_a_recipient() {
     let confirmation_links = app.get_confirmation_links(email_request);
 
     // Act
-    let query = sqlx::query!("Select status From links_tokens")
+    let query = sqlx::query!(r#"SELECT status::text AS "status!" FROM links_tokens"#)
         .fetch_one(&app.db_pool)
         .await
         .expect("Failed to fetch saved recipient.");
@@ -92,9 +121,332 @@ async fn clicking_on_the_confirmation_link_confirms_a_recipient() {
 
     assert_eq!(saved.name, "hamada");
     assert_eq!(saved.email, "hamada@yahoo.com");
-    let query = sqlx::query!("Select status From links_tokens")
+    let query = sqlx::query!(r#"SELECT status::text AS "status!" FROM links_tokens"#)
         .fetch_one(&app.db_pool)
         .await
         .expect("Failed to fetch saved recipient.");
     assert_eq!(query.status, "confirmed");
 }
+
+#[tokio::test]
+async fn expired_confirmation_links_are_rejected_with_a_410() {
+    // Arrange
+    let app = spawn_app().await;
+    app.accept_emails().await;
+    let links_body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        ..Default::default()
+    };
+    let (_, short_id) = app.post_links(links_body).await;
+    app.post_link_recipeints(
+        FormData {
+            name: Some("hamada"),
+            email: Some("hamada@yahoo.com"),
+        },
+        &short_id,
+    )
+    .await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    sqlx::query!("UPDATE links_tokens SET expiration_date = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .expect("Failed to expire the link token.");
+
+    // Act
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 410);
+    assert!(response.text().await.unwrap().contains("expired"));
+    let query = sqlx::query!(r#"SELECT status::text AS "status!" FROM links_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved recipient.");
+    assert_eq!(query.status, "pending");
+}
+
+#[tokio::test]
+async fn confirmation_links_are_valid_for_the_validity_of_the_link() {
+    // Arrange
+    let app = spawn_app().await;
+    app.accept_emails().await;
+    let links_body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        link_token_validity_hours: Some(2),
+        ..Default::default()
+    };
+    let (_, short_id) = app.post_links(links_body).await;
+
+    // Act
+    app.post_link_recipeints(
+        FormData {
+            name: Some("hamada"),
+            email: Some("hamada@yahoo.com"),
+        },
+        &short_id,
+    )
+    .await;
+
+    // Assert
+    let query = sqlx::query!(r#"SELECT expiration_date - now() AS "validity!" FROM links_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved link token.");
+    let two_hours = 2 * 60 * 60 * 1_000_000;
+    assert!(query.validity.microseconds <= two_hours);
+    assert!(query.validity.microseconds > two_hours - 60 * 1_000_000);
+}
+
+#[tokio::test]
+async fn confirmed_recipients_lose_access_once_their_grant_expires() {
+    // Arrange
+    let app = spawn_app().await;
+    app.accept_emails().await;
+    let links_body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        grant_validity_hours: Some(1),
+        ..Default::default()
+    };
+    let (_, short_id) = app.post_links(links_body).await;
+    app.post_link_recipeints(
+        FormData {
+            name: Some("hamada"),
+            email: Some("hamada@yahoo.com"),
+        },
+        &short_id,
+    )
+    .await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let access_link = || async {
+        app.post_get_link(
+            FormData {
+                name: Some("hamada"),
+                email: Some("hamada@yahoo.com"),
+            },
+            &short_id,
+        )
+        .await
+    };
+    assert_eq!(access_link().await.status().as_u16(), 303);
+
+    // Act
+    sqlx::query!("UPDATE links_tokens SET expiration_date = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .expect("Failed to expire the grant.");
+
+    // Assert
+    assert_eq!(access_link().await.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn confirming_returns_403_once_max_recipients_is_reached() {
+    // Arrange
+    let app = spawn_app().await;
+    app.accept_emails().await;
+    let (_, short_id) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://www.example.com"),
+            max_recipients: Some(1),
+            ..Default::default()
+        })
+        .await;
+    for (name, email) in [("hamada", "hamada@yahoo.com"), ("johnny", "depp@yahoo.com")] {
+        let body = FormData {
+            name: Some(name),
+            email: Some(email),
+        };
+        app.post_link_recipeints(body, &short_id).await;
+    }
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let first = app.get_confirmation_links(&email_requests[0]);
+    let second = app.get_confirmation_links(&email_requests[1]);
+
+    // Act
+    let first_response = reqwest::get(first.html).await.unwrap();
+    let second_response = reqwest::get(second.html).await.unwrap();
+
+    // Assert
+    assert_eq!(first_response.status().as_u16(), 200);
+    assert_eq!(second_response.status().as_u16(), 403);
+    let statuses = sqlx::query!(
+        r#"
+    SELECT link_recipients.email, links_tokens.status::text AS "status!" FROM links_tokens
+    JOIN link_recipients ON link_recipients.id = links_tokens.recepient_id
+    ORDER BY link_recipients.email
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .expect("Failed to fetch the link tokens.");
+    assert_eq!(statuses[0].email, "depp@yahoo.com");
+    assert_eq!(statuses[0].status, "pending");
+    assert_eq!(statuses[1].status, "confirmed");
+}
+
+#[tokio::test]
+async fn confirming_remembers_the_recipient_so_the_form_is_skipped() {
+    // Arrange
+    let app = spawn_app().await;
+    app.accept_emails().await;
+    let links_body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        ..Default::default()
+    };
+    let (_, short_id) = app.post_links(links_body).await;
+    let body = FormData {
+        name: Some("hamada"),
+        email: Some("hamada@yahoo.com"),
+    };
+    app.post_link_recipeints(body, &short_id).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    // Act
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Assert
+    let set_cookie = response.headers()["set-cookie"].to_str().unwrap();
+    assert!(set_cookie.contains("HttpOnly"));
+    assert!(set_cookie.contains("Max-Age="));
+    let cookie = session_cookie(&response).expect("No session cookie was set");
+    let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .get(format!("{}/{}", app.address, short_id))
+        .header("Cookie", cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers()["Location"], "https://www.example.com/");
+    let saved = sqlx::query!("SELECT click_count FROM links WHERE id = $1", short_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.click_count, 1);
+}
+
+#[tokio::test]
+async fn confirming_redirects_to_the_target_url_when_configured() {
+    // Arrange
+    let app =
+        spawn_app_with(|c| c.application.after_confirmation = AfterConfirmation::Redirect).await;
+    app.accept_emails().await;
+    let (short_id, _) = app.register().await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_links(email_request).html;
+
+    // Act
+    let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .get(confirmation_link)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers()["Location"], "https://www.example.com/");
+    assert!(session_cookie(&response).is_some());
+    let saved = sqlx::query!("SELECT click_count FROM links WHERE id = $1", short_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.click_count, 1);
+}
+
+#[tokio::test]
+async fn confirming_shows_a_continue_button_when_configured() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.after_confirmation = AfterConfirmation::ContinuePage;
+    })
+    .await;
+    app.accept_emails().await;
+    let (short_id, _) = app.register().await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_links(email_request).html;
+
+    // Act
+    let response = reqwest::get(confirmation_link).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(session_cookie(&response).is_some());
+    let html = response.text().await.unwrap();
+    assert!(html.contains(&format!(r#"href="http://127.0.0.1:8080/{}""#, short_id)));
+}
+
+#[tokio::test]
+async fn the_continue_button_keeps_the_path_prefix_of_the_base_url() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.base_url = ApplicationBaseUrl::parse("http://127.0.0.1:8080/go").unwrap();
+        c.application.after_confirmation = AfterConfirmation::ContinuePage;
+    })
+    .await;
+    app.accept_emails().await;
+    let (short_id, _) = app.register().await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let mut confirmation_link = app.get_confirmation_links(email_request).html;
+    assert!(confirmation_link.path().starts_with("/go/"));
+    // the test server has no proxy stripping the prefix
+    let path = confirmation_link
+        .path()
+        .trim_start_matches("/go")
+        .to_string();
+    confirmation_link.set_path(&path);
+
+    // Act
+    let response = reqwest::get(confirmation_link).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains(&format!(r#"href="http://127.0.0.1:8080/go/{}""#, short_id)));
+    assert!(html.contains(r#"href="/go/templates/output.css""#));
+}
+
+#[tokio::test]
+async fn confirmation_links_can_only_be_used_once() {
+    // Arrange
+    let app = spawn_app().await;
+    app.accept_emails().await;
+    let (short_id, _) = app.register().await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_links(email_request).html;
+    let first_response = reqwest::get(confirmation_link.clone()).await.unwrap();
+    assert!(session_cookie(&first_response).is_some());
+    let confirmed_at = sqlx::query!("SELECT confirmed_at FROM links_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .confirmed_at;
+    assert!(confirmed_at.is_some());
+
+    // Act
+    let response = reqwest::get(confirmation_link).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(session_cookie(&response).is_none());
+    let html = response.text().await.unwrap();
+    assert!(html.contains("Already Confirmed"));
+    assert!(html.contains(&format!(r#"href="http://127.0.0.1:8080/{}""#, short_id)));
+    let saved = sqlx::query!("SELECT confirmed_at FROM links_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.confirmed_at, confirmed_at);
+}
@@ -1,11 +1,18 @@
 use reqwest::StatusCode;
-use url_shortener_with_a_twist::routes::LinkTarget;
+use url_shortener_with_a_twist::{
+    configuration::{IdGeneratorSettings, get_configuration},
+    domain::ApplicationBaseUrl,
+    routes::LinkTarget,
+    startup::Application,
+};
 use wiremock::{
     Mock, ResponseTemplate,
     matchers::{method, path},
 };
 
-use crate::helpers::{FormData, spawn_app};
+use crate::helpers::{
+    FormData, TestApp, extract_management_token, session_cookie, spawn_app, spawn_app_with,
+};
 
 #[tokio::test]
 async fn create_link_returns_200_for_valid_url() {
@@ -13,6 +20,7 @@ async fn create_link_returns_200_for_valid_url() {
     let app = spawn_app().await;
     let body = LinkTarget {
         target_url: String::from("https://www.example.com"),
+        ..Default::default()
     };
 
     // Act
@@ -34,6 +42,7 @@ async fn create_link_returns_400_for_invalid_url() {
     let app = spawn_app().await;
     let body = LinkTarget {
         target_url: String::from("definetly-not-a-valid-url"),
+        ..Default::default()
     };
     // Act
 
@@ -50,6 +59,7 @@ async fn give_access_to_target_link_200() {
 
     let body = LinkTarget {
         target_url: String::from("https://www.example.com"),
+        ..Default::default()
     };
 
     Mock::given(path("/email"))
@@ -98,27 +108,959 @@ async fn give_access_to_target_link_200() {
     .await
     .expect("Failed to update link token status.");
 
+    let response = app.post_get_link(body, &saved.id).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+}
+
+#[tokio::test]
+async fn redirect_returns_400_for_nonexistent_link() {
+    let app = spawn_app().await;
+    let response = reqwest::Client::new()
+        .get(format!("{}/nonexistent", &app.address))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn create_link_returns_400_for_an_invalid_allowlist() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        allowlist: Some(String::from("@ourcompany.com, not-an-email")),
+        ..Default::default()
+    };
+
+    // Act
+    let (response, _) = app.post_links(body).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn link_access_page_returns_410_for_an_expired_link() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        expires_at: Some((chrono::Utc::now() - chrono::Duration::hours(1)).to_rfc3339()),
+        ..Default::default()
+    };
+    let (_, link_id) = app.post_links(body).await;
+
+    // Act
+    let response = reqwest::get(format!("{}/{}", &app.address, link_id))
+        .await
+        .expect("Failed to send request");
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::GONE);
+    assert!(response.text().await.unwrap().contains("expired"));
+}
+
+#[tokio::test]
+async fn link_access_page_returns_403_for_a_link_not_yet_available() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        active_from: Some(String::from("2999-01-01T09:30")),
+        ..Default::default()
+    };
+    let (_, link_id) = app.post_links(body).await;
+
+    // Act
+    let response = reqwest::get(format!("{}/{}", &app.address, link_id))
+        .await
+        .expect("Failed to send request");
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains("2999-01-01 09:30 UTC")
+    );
+}
+
+#[tokio::test]
+async fn dates_are_saved_in_utc() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        // what the create form sends for 09:30 in UTC+2
+        active_from: Some(String::from("2999-01-01T07:30:00.000Z")),
+        // without a timezone, as sent when js is off
+        expires_at: Some(String::from("2999-01-02T09:30")),
+        ..Default::default()
+    };
+
+    // Act
+    let (_, link_id) = app.post_links(body).await;
+
+    // Assert
+    let saved = sqlx::query!(
+        "SELECT active_from, expires_at FROM links WHERE id = $1",
+        link_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch saved link.");
+    assert_eq!(
+        saved.active_from.unwrap().to_rfc3339(),
+        "2999-01-01T07:30:00+00:00"
+    );
+    assert_eq!(
+        saved.expires_at.unwrap().to_rfc3339(),
+        "2999-01-02T09:30:00+00:00"
+    );
+}
+
+#[tokio::test]
+async fn the_create_form_sends_its_dates_in_utc() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let html = reqwest::get(&app.address)
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(html.contains(r#"hx-vals='js:{"active_from": utcDate("active_from"), "expires_at": utcDate("expires_at")}'"#));
+    assert!(html.contains("toISOString()"));
+}
+
+#[tokio::test]
+async fn access_link_returns_410_once_the_link_expired() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        expires_at: Some(String::from("2999-01-01T09:30")),
+        ..Default::default()
+    };
+    let (_, link_id) = app.post_links(body).await;
+    sqlx::query!("UPDATE links SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .expect("Failed to expire the link.");
+
+    // Act
+    let response = app
+        .post_get_link(
+            FormData {
+                name: Some("johnny"),
+                email: Some("depp@yahoo.com"),
+            },
+            &link_id,
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::GONE);
+    assert!(response.headers().get("HX-Redirect").is_none());
+}
+
+#[tokio::test]
+async fn create_link_returns_400_for_an_invalid_activation_window() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (Some("not-a-date"), None, "invalid active_from"),
+        (None, Some("2025-13-01T00:00"), "invalid expires_at"),
+        (
+            Some("2030-01-02T00:00"),
+            Some("2030-01-01T00:00"),
+            "expires_at before active_from",
+        ),
+    ];
+
+    for (active_from, expires_at, description) in test_cases {
+        // Act
+        let (response, _) = app
+            .post_links(LinkTarget {
+                target_url: String::from("https://www.example.com"),
+                active_from: active_from.map(String::from),
+                expires_at: expires_at.map(String::from),
+                ..Default::default()
+            })
+            .await;
+
+        // Assert
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "The API did not return a 400 Bad Request when the payload had an {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn access_link_returns_403_once_max_clicks_is_reached() {
+    // Arrange
+    let app = spawn_app().await;
+    app.accept_emails().await;
+    let (_, link_id) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://www.example.com"),
+            max_clicks: Some(1),
+            ..Default::default()
+        })
+        .await;
+    let body = FormData {
+        name: Some("johnny"),
+        email: Some("depp@yahoo.com"),
+    };
+    app.post_link_recipeints(body, &link_id).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html).await.unwrap();
+    let get_link = || {
+        app.post_get_link(
+            FormData {
+                name: Some("johnny"),
+                email: Some("depp@yahoo.com"),
+            },
+            &link_id,
+        )
+    };
+
+    // Act
+    let first = get_link().await;
+    let second = get_link().await;
+
+    // Assert
+    assert_eq!(first.status(), StatusCode::SEE_OTHER);
+    assert_eq!(second.status(), StatusCode::FORBIDDEN);
+    assert!(second.headers().get("HX-Redirect").is_none());
+    let saved = sqlx::query!("SELECT click_count FROM links WHERE id = $1", link_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved link.");
+    assert_eq!(saved.click_count, 1);
+
+    let response = reqwest::get(format!("{}/{}", &app.address, link_id))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn create_link_returns_400_for_a_limit_that_is_not_positive() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (Some(0), None, "max_clicks of 0"),
+        (None, Some(-1), "negative max_recipients"),
+    ];
+
+    for (max_clicks, max_recipients, description) in test_cases {
+        // Act
+        let (response, _) = app
+            .post_links(LinkTarget {
+                target_url: String::from("https://www.example.com"),
+                max_clicks,
+                max_recipients,
+                ..Default::default()
+            })
+            .await;
+
+        // Assert
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "The API did not return a 400 Bad Request when the payload had a {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn create_link_uses_the_custom_alias() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let (response, link_id) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://www.example.com"),
+            alias: Some(String::from("q3-roadmap")),
+            ..Default::default()
+        })
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(link_id, "q3-roadmap");
+    let response = reqwest::get(format!("{}/q3-roadmap", &app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn create_link_returns_409_when_the_alias_is_taken() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = || LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        alias: Some(String::from("q3-roadmap")),
+        ..Default::default()
+    };
+    app.post_links(body()).await;
+
+    // Act
+    let (response, _) = app.post_links(body()).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert!(response.text().await.unwrap().contains("already taken"));
+}
+
+#[tokio::test]
+async fn create_link_returns_400_for_an_invalid_or_reserved_alias() {
+    // Arrange
+    let app = spawn_app().await;
+
+    for alias in ["a/b", "x", "health_check", "get_link"] {
+        // Act
+        let (response, _) = app
+            .post_links(LinkTarget {
+                target_url: String::from("https://www.example.com"),
+                alias: Some(String::from(alias)),
+                ..Default::default()
+            })
+            .await;
+
+        // Assert
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "The API did not reject the alias {}.",
+            alias
+        );
+    }
+}
+
+#[tokio::test]
+async fn create_link_retries_when_the_generated_id_is_taken() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.id_generator = IdGeneratorSettings::Counter { key: 7 };
+    })
+    .await;
+    let body = || LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        ..Default::default()
+    };
+    app.post_links(body()).await;
+    // the next link gets the same counter, and so the same id, as the first one
+    sqlx::query!("SELECT setval('link_id_counter', 1, false)")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to reset the counter.");
+
+    // Act
+    let (response, _) = app.post_links(body()).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let saved = sqlx::query!(r#"SELECT COUNT(DISTINCT id) AS "count!" FROM links"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to count links.");
+    assert_eq!(saved.count, 2);
+}
+
+#[tokio::test]
+async fn create_link_returns_500_once_every_attempt_collided() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.id_generator = IdGeneratorSettings::Random { length: 1 };
+    })
+    .await;
+    // take every possible id of a single base62 character
+    sqlx::query!(
+        r#"
+    INSERT INTO links (id, target_url, created_at)
+    SELECT chr(code), 'https://www.example.com/', now()
+    FROM generate_series(48, 122) AS code
+    WHERE chr(code) ~ '[0-9A-Za-z]'
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to take every id.");
+
+    // Act
+    let (response, _) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://www.example.com"),
+            ..Default::default()
+        })
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let saved = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM links"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to count links.");
+    assert_eq!(saved.count, 62);
+}
+
+#[tokio::test]
+async fn create_link_uses_the_configured_id_generator() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.id_generator = IdGeneratorSettings::Words { count: 3 };
+    })
+    .await;
+
+    // Act
+    let (response, link_id) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://www.example.com"),
+            ..Default::default()
+        })
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(link_id.split('-').count(), 3);
+}
+
+#[tokio::test]
+async fn create_link_with_dedupe_returns_the_existing_link_of_the_owner() {
+    // Arrange
+    let app = spawn_app().await;
+    let (response, first_id) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://Example.com/docs/?b=2&a=1"),
+            allowlist: Some(String::from("depp@yahoo.com, @ourcompany.com")),
+            ..Default::default()
+        })
+        .await;
+    let management_token = extract_management_token(&response.text().await.unwrap());
+
+    // Act
+    let (response, second_id) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://example.com:443/docs?a=1&b=2"),
+            allowlist: Some(String::from("@ourcompany.com depp@yahoo.com")),
+            management_token: Some(management_token),
+            dedupe: Some(true),
+            ..Default::default()
+        })
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.text().await.unwrap().contains(&first_id));
+    assert_eq!(first_id, second_id);
+    let saved = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM links"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to count links.");
+    assert_eq!(saved.count, 1);
+}
+
+#[tokio::test]
+async fn create_link_only_dedupes_on_request_and_for_the_same_policy() {
+    // Arrange
+    let app = spawn_app().await;
+    let (response, _) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://www.example.com"),
+            ..Default::default()
+        })
+        .await;
+    let management_token = extract_management_token(&response.text().await.unwrap());
+    let test_cases = vec![
+        (
+            LinkTarget {
+                target_url: String::from("https://www.example.com"),
+                management_token: Some(management_token.clone()),
+                ..Default::default()
+            },
+            "dedupe was not asked for",
+        ),
+        (
+            LinkTarget {
+                target_url: String::from("https://www.example.com"),
+                management_token: Some(management_token.clone()),
+                max_clicks: Some(10),
+                dedupe: Some(true),
+                ..Default::default()
+            },
+            "the policy is different",
+        ),
+        (
+            LinkTarget {
+                target_url: String::from("https://www.example.com"),
+                dedupe: Some(true),
+                ..Default::default()
+            },
+            "the owner is different",
+        ),
+    ];
+
+    for (expected_links, (body, description)) in (2..).zip(test_cases) {
+        // Act
+        let (response, _) = app.post_links(body).await;
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::OK);
+        let saved = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM links"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .expect("Failed to count links.");
+        assert_eq!(
+            saved.count, expected_links,
+            "No new link was created when {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn create_link_returns_400_with_the_reason_for_an_unsafe_url() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = [
+        ("javascript:alert(1)", "javascript"),
+        ("file:///etc/passwd", "file"),
+        ("http://169.254.169.254/latest/meta-data", "private address"),
+        ("http://192.168.1.1/admin", "private address"),
+        ("http://localhost:5432", "private address"),
+    ];
+
+    for (target_url, reason) in test_cases {
+        // Act
+        let body = LinkTarget {
+            target_url: target_url.to_string(),
+            ..Default::default()
+        };
+        let (response, _) = app.post_links(body).await;
+
+        // Assert
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "{} was accepted",
+            target_url
+        );
+        let html = response.text().await.unwrap();
+        assert!(html.contains(reason), "{} is missing from {}", reason, html);
+    }
+    let saved = sqlx::query!("SELECT count(*) AS \"count!\" FROM links")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.count, 0);
+}
+
+#[tokio::test]
+async fn create_link_rejects_our_own_base_url() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.base_url = ApplicationBaseUrl::parse("https://short.example.com").unwrap()
+    })
+    .await;
+    let body = LinkTarget {
+        target_url: String::from("https://short.example.com/abc"),
+        ..Default::default()
+    };
+
+    // Act
+    let (response, _) = app.post_links(body).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn create_link_rejects_blocklisted_domains() {
+    // Arrange
+    let blocklist = std::env::temp_dir().join(format!("blocklist-{}.txt", uuid::Uuid::new_v4()));
+    std::fs::write(&blocklist, "# known phishing\nevil.example\n\n").unwrap();
+    let app = spawn_app_with(|c| {
+        c.application.blocklist_path = Some(blocklist.to_string_lossy().into_owned());
+    })
+    .await;
+
+    for target_url in ["https://evil.example", "https://login.evil.example/"] {
+        // Act
+        let body = LinkTarget {
+            target_url: target_url.to_string(),
+            ..Default::default()
+        };
+        let (response, _) = app.post_links(body).await;
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response.text().await.unwrap().contains("is blocked"));
+    }
+    let body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        ..Default::default()
+    };
+    let (response, _) = app.post_links(body).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn the_application_refuses_to_start_with_a_missing_blocklist() {
+    // Arrange
+    let mut configuration = get_configuration().expect("Failed to read configuration.");
+    configuration.application.port = 0;
+    configuration.application.blocklist_path = Some(String::from("no/such/blocklist.txt"));
+
+    // Act
+    let application = Application::build(configuration).await;
+
+    // Assert
+    assert!(application.is_err());
+}
+
+#[tokio::test]
+async fn the_application_refuses_to_start_with_a_short_hmac_secret() {
+    // Arrange
+    let mut configuration = get_configuration().expect("Failed to read configuration.");
+    configuration.application.port = 0;
+    configuration.application.hmac_secret = secrecy::SecretString::from("too short");
+
+    // Act
+    let application = Application::build(configuration).await;
+
+    // Assert
+    assert!(application.is_err());
+}
+
+/// A link with a preview hamada already confirmed.
+async fn link_with_a_preview(app: &TestApp) -> String {
+    app.accept_emails().await;
+    app.verify_recipient_on(LinkTarget {
+        target_url: String::from("https://docs.example.com/q3"),
+        show_preview: Some(true),
+        title: Some(String::from("Q3 roadmap")),
+        description: Some(String::from("Draft, please don't share")),
+        ..Default::default()
+    })
+    .await
+}
+
+async fn click_count(app: &TestApp, link_id: &str) -> i32 {
+    sqlx::query!("SELECT click_count FROM links WHERE id = $1", link_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved link.")
+        .click_count
+}
+
+#[tokio::test]
+async fn access_link_shows_the_preview_instead_of_redirecting() {
+    // Arrange
+    let app = spawn_app().await;
+    let link_id = link_with_a_preview(&app).await;
+
+    // Act
+    let response = get_link_request(&app, &link_id)
+        .header("HX-Request", "true")
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("HX-Redirect").is_none());
+    let html = response.text().await.unwrap();
+    assert!(html.contains("docs.example.com"));
+    assert!(html.contains("Q3 roadmap"));
+    assert!(html.contains("Draft, please don&#39;t share"));
+    assert!(html.contains(&format!(
+        r#"href="http://127.0.0.1:8080/{}/continue""#,
+        link_id
+    )));
+    // nobody was redirected yet
+    assert_eq!(click_count(&app, &link_id).await, 0);
+    let redirects =
+        sqlx::query!("SELECT count(*) AS \"count!\" FROM link_events WHERE kind = 'redirect'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap()
+            .count;
+    assert_eq!(redirects, 0);
+}
+
+#[tokio::test]
+async fn continuing_from_the_preview_counts_the_click_and_redirects() {
+    // Arrange
+    let app = spawn_app().await;
+    let link_id = link_with_a_preview(&app).await;
+    let preview = get_link_request(&app, &link_id).send().await.unwrap();
+    let cookie = session_cookie(&preview).unwrap();
+
+    // Act
     let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
         .build()
         .unwrap()
-        .post(&format!("{}/get_link/{}", &app.address, saved.id))
-        .form(&body)
+        .get(format!("{}/{}/continue", app.address, link_id))
+        .header(reqwest::header::COOKIE, cookie)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(
+        response.headers()[reqwest::header::LOCATION],
+        "https://docs.example.com/q3"
+    );
+    assert_eq!(click_count(&app, &link_id).await, 1);
+}
+
+#[tokio::test]
+async fn continuing_without_a_remembered_recipient_goes_back_to_the_short_link() {
+    // Arrange
+    let app = spawn_app().await;
+    let link_id = link_with_a_preview(&app).await;
+
+    // Act
+    let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .get(format!("{}/{}/continue", app.address, link_id))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert!(
+        response.headers()[reqwest::header::LOCATION]
+            .to_str()
+            .unwrap()
+            .ends_with(&format!("/{}", link_id))
+    );
+    assert_eq!(click_count(&app, &link_id).await, 0);
+}
+
+#[tokio::test]
+async fn create_link_returns_400_for_a_title_that_is_too_long() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = LinkTarget {
+        target_url: String::from("https://www.example.com"),
+        show_preview: Some(true),
+        title: Some("a".repeat(201)),
+        ..Default::default()
+    };
+
+    // Act
+    let (response, _) = app.post_links(body).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+fn get_link_request(app: &TestApp, link_id: &str) -> reqwest::RequestBuilder {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .post(format!("{}/get_link/{}", &app.address, link_id))
+        .form(&FormData {
+            name: Some("hamada"),
+            email: Some("hamada@yahoo.com"),
+        })
+}
+
+#[tokio::test]
+async fn access_link_redirects_htmx_requests_with_hx_redirect() {
+    // Arrange
+    let app = spawn_app().await;
+    app.accept_emails().await;
+    let link_id = app.link_with_a_verified_recipient().await;
+
+    // Act
+    let response = get_link_request(&app, &link_id)
+        .header("HX-Request", "true")
         .send()
         .await
         .expect("Failed to send request");
 
     // Assert
     assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(
+        response.headers()["HX-Redirect"],
+        "https://www.example.com/"
+    );
+    assert!(response.headers().get("Location").is_none());
 }
 
 #[tokio::test]
-async fn redirect_returns_400_for_nonexistent_link() {
+async fn access_link_redirects_plain_form_posts_with_location() {
+    // Arrange
     let app = spawn_app().await;
-    let response = reqwest::Client::new()
-        .get(&format!("{}/nonexistent", &app.address))
+    app.accept_emails().await;
+    let link_id = app.link_with_a_verified_recipient().await;
+
+    // Act
+    let response = get_link_request(&app, &link_id)
+        .header("Accept", "text/html")
         .send()
         .await
         .expect("Failed to send request");
 
-    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    // Assert
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()["Location"], "https://www.example.com/");
+    assert!(response.headers().get("HX-Redirect").is_none());
+}
+
+#[tokio::test]
+async fn access_link_returns_the_target_url_to_json_clients() {
+    // Arrange
+    let app = spawn_app().await;
+    app.accept_emails().await;
+    let link_id = app.link_with_a_verified_recipient().await;
+
+    // Act
+    let response = get_link_request(&app, &link_id)
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["target_url"], "https://www.example.com/");
+}
+
+#[tokio::test]
+async fn access_link_remembers_the_recipient() {
+    // Arrange
+    let app = spawn_app().await;
+    app.accept_emails().await;
+    let link_id = app.link_with_a_verified_recipient().await;
+
+    // Act
+    let response = get_link_request(&app, &link_id)
+        .header("HX-Request", "true")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    // Assert
+    let cookie = session_cookie(&response).expect("No session cookie was set");
+    let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .get(format!("{}/{}", app.address, link_id))
+        .header("Cookie", cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+}
+
+#[tokio::test]
+async fn link_access_page_shows_the_form_without_a_genuine_cookie() {
+    // Arrange
+    let app = spawn_app().await;
+    app.accept_emails().await;
+    let link_id = app.link_with_a_verified_recipient().await;
+    let response = get_link_request(&app, &link_id)
+        .header("HX-Request", "true")
+        .send()
+        .await
+        .expect("Failed to send request");
+    let cookie = session_cookie(&response).unwrap();
+    let (_, other_link_id) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://www.example.com/other"),
+            ..Default::default()
+        })
+        .await;
+    // the signature no longer matches the value
+    let tampered = format!("{}0", cookie);
+
+    for (cookie, link_id) in [
+        // the recipient never registered for the other link
+        (cookie.as_str(), other_link_id.as_str()),
+        (tampered.as_str(), link_id.as_str()),
+    ] {
+        // Act
+        let response = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap()
+            .get(format!("{}/{}", app.address, link_id))
+            .header("Cookie", cookie)
+            .send()
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.text().await.unwrap().contains("/get_link/"));
+    }
+}
+
+#[tokio::test]
+async fn pages_and_forms_keep_the_path_prefix_of_the_base_url() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.base_url = ApplicationBaseUrl::parse("http://127.0.0.1:8080/go").unwrap();
+    })
+    .await;
+
+    // Act
+    // the test server has no proxy stripping the prefix
+    let index = reqwest::get(&app.address)
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let (response, link_id) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://www.example.com"),
+            ..Default::default()
+        })
+        .await;
+    let access_page = reqwest::get(format!("{}/{}", app.address, link_id))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(index.contains(r#"href="/go/templates/output.css""#));
+    assert!(index.contains(r#"action="/go/create""#));
+    assert!(index.contains(r#"hx-post="/go/manage""#));
+    let created = response.text().await.unwrap();
+    assert!(created.contains(&format!("http://127.0.0.1:8080/go/{}", link_id)));
+    assert!(access_page.contains(&format!(r#"hx-post="/go/link_recipients/{}""#, link_id)));
+    assert!(access_page.contains(&format!(r#"hx-post="/go/get_link/{}""#, link_id)));
 }
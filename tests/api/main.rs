@@ -1,5 +1,12 @@
+mod api_keys;
+mod api_v1;
+mod email_outbox;
 mod health_check;
 mod helpers;
+mod link_approvals;
+mod link_events;
+mod link_management;
 mod link_recipients;
 mod link_tokens_confirm;
 mod links;
+mod sign_in;
@@ -0,0 +1,203 @@
+use reqwest::StatusCode;
+use url_shortener_with_a_twist::{configuration::SignInMode, routes::LinkTarget};
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{FormData, TestApp, session_cookie, spawn_app_with};
+
+async fn spawn_magic_link_app() -> TestApp {
+    let app = spawn_app_with(|c| c.application.sign_in = SignInMode::MagicLink).await;
+    app.accept_emails().await;
+    app
+}
+
+/// Ask for a sign-in link, the email is delivered by the time it returns.
+async fn request_sign_in(app: &TestApp, link_id: &str, email: &str) -> reqwest::Response {
+    let response = reqwest::Client::new()
+        .post(format!("{}/sign_in/{}", app.address, link_id))
+        .form(&[("email", email)])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    app.wait_for_outbox().await;
+    response
+}
+
+fn no_redirects() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn the_access_page_asks_for_an_email_only() {
+    // Arrange
+    let app = spawn_magic_link_app().await;
+    let link_id = app.link_with_a_verified_recipient().await;
+
+    // Act
+    let html = reqwest::get(format!("{}/{}", app.address, link_id))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(html.contains(&format!("/sign_in/{}", link_id)));
+    assert!(!html.contains(&format!("/get_link/{}", link_id)));
+}
+
+#[tokio::test]
+async fn name_and_email_are_not_enough_to_get_the_link() {
+    // Arrange
+    let app = spawn_magic_link_app().await;
+    let link_id = app.link_with_a_verified_recipient().await;
+
+    // Act
+    let response = app
+        .post_get_link(
+            FormData {
+                name: Some("hamada"),
+                email: Some("hamada@yahoo.com"),
+            },
+            &link_id,
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(response.headers().get("HX-Redirect").is_none());
+}
+
+#[tokio::test]
+async fn the_sign_in_link_grants_access_once() {
+    // Arrange
+    let app = spawn_magic_link_app().await;
+    let link_id = app.link_with_a_verified_recipient().await;
+    let response = request_sign_in(&app, &link_id, "hamada@yahoo.com").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let email_request = &app.email_server.received_requests().await.unwrap()[1];
+    let sign_in_link = app.get_confirmation_links(email_request).html;
+
+    // Act
+    let response = no_redirects()
+        .get(sign_in_link.clone())
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(
+        response.headers()["Location"],
+        format!("http://127.0.0.1:8080/{}", link_id)
+    );
+    let cookie = session_cookie(&response).expect("No session cookie was set");
+    let response = no_redirects()
+        .get(format!("{}/{}", app.address, link_id))
+        .header("Cookie", cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()["Location"], "https://www.example.com/");
+
+    let response = no_redirects().get(sign_in_link).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::GONE);
+    assert!(session_cookie(&response).is_none());
+}
+
+#[tokio::test]
+async fn expired_sign_in_links_are_refused() {
+    // Arrange
+    let app = spawn_magic_link_app().await;
+    let link_id = app.link_with_a_verified_recipient().await;
+    request_sign_in(&app, &link_id, "hamada@yahoo.com").await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[1];
+    let sign_in_link = app.get_confirmation_links(email_request).html;
+    sqlx::query!("UPDATE sign_in_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = no_redirects().get(sign_in_link).send().await.unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::GONE);
+}
+
+#[tokio::test]
+async fn emails_without_access_get_the_same_answer_but_no_email() {
+    // Arrange
+    let app = spawn_magic_link_app().await;
+    let link_id = app.link_with_a_verified_recipient().await;
+    let (_, other_link_id) = app
+        .post_links(LinkTarget {
+            target_url: String::from("https://www.example.com/other"),
+            ..Default::default()
+        })
+        .await;
+
+    for (link_id, email) in [
+        (link_id.as_str(), "stranger@yahoo.com"),
+        // hamada never registered for the other link
+        (other_link_id.as_str(), "hamada@yahoo.com"),
+    ] {
+        // Act
+        let response = request_sign_in(&app, link_id, email).await;
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.text().await.unwrap().contains(email));
+    }
+    // only the confirmation email was sent
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn sign_in_links_are_sent_once_per_cooldown_without_telling() {
+    // Arrange
+    let app = spawn_magic_link_app().await;
+    let link_id = app.link_with_a_verified_recipient().await;
+    let first_response = request_sign_in(&app, &link_id, "hamada@yahoo.com").await;
+    let first_html = first_response.text().await.unwrap();
+
+    // Act
+    let response = request_sign_in(&app, &link_id, "hamada@yahoo.com").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), first_html);
+    // the confirmation email and a single sign-in email
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn requesting_a_sign_in_link_succeeds_when_the_email_cant_be_sent_yet() {
+    // Arrange
+    let app = spawn_magic_link_app().await;
+    let link_id = app.link_with_a_verified_recipient().await;
+    app.email_server.reset().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = request_sign_in(&app, &link_id, "hamada@yahoo.com").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let queued =
+        sqlx::query!("SELECT recipient FROM email_outbox WHERE subject = 'Your sign-in link'")
+            .fetch_one(&app.db_pool)
+            .await
+            .expect("The sign-in email was not queued.");
+    assert_eq!(queued.recipient, "hamada@yahoo.com");
+}